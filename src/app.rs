@@ -1,15 +1,96 @@
-use std::{thread, sync::mpsc::{Receiver, self, TryRecvError}, time::{Instant, Duration}};
+use std::{path::Path, sync::{Arc, atomic::{AtomicBool, Ordering}, mpsc::{Receiver, self, TryRecvError}}, time::Duration};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{any::Any, panic::{self, AssertUnwindSafe}, sync::mpsc::Sender, thread};
 
 use eframe::{egui, epi};
+use instant::Instant;
+use serde::{Deserialize, Serialize};
 
-use crate::search;
+use crate::archive::ArchiveMode;
+use crate::bench::Bench;
+use crate::cache::LinkCache;
+use crate::charts::Charts;
+use crate::constraints::{join_list, title_of, Constraints};
+use crate::context::LinkContext;
+use crate::graph::{ExploredGraph, GraphFormat, GraphRecording};
+use crate::first_link::{ChainEnd, FirstLinkChain};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::first_link;
+use crate::history::{History, HistoryAction, HistoryEntry};
+use crate::rate_limit::RateLimiter;
+use crate::search::{FetchOptions, FrontierEvent, Limits, SearchConfig, SearchOutcome, WorkerStatus, WIKI_URL};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::searcher::{Observer, Progress, Searcher};
+use crate::title_input::TitleInput;
+use crate::tree_view::TreeView;
+#[cfg(target_arch = "wasm32")]
+use crate::web;
+
+/// A worker that died is started again this many times.
+const WORKER_RESTARTS: usize = 2;
+/// Worker threads of all the running searches together.
+const MAX_WORKERS: usize = 100;
+/// Wikis that can be picked as the source, any other url can be typed in.
+const SOURCES: [(&str, &str); 6] = [
+    ("English Wikipedia", WIKI_URL),
+    ("Simple English Wikipedia", "https://simple.wikipedia.org/wiki/"),
+    ("German Wikipedia", "https://de.wikipedia.org/wiki/"),
+    ("French Wikipedia", "https://fr.wikipedia.org/wiki/"),
+    ("Spanish Wikipedia", "https://es.wikipedia.org/wiki/"),
+    ("Russian Wikipedia", "https://ru.wikipedia.org/wiki/"),
+];
+
+#[cfg(feature = "persistence")]
+const HISTORY_KEY: &str = "history";
+#[cfg(feature = "persistence")]
+const SETTINGS_KEY: &str = "settings";
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SearchMode {
+    ShortestPath,
+    FirstLink,  // "Getting to Philosophy": follow the first prose link of every page
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum SearchResult {
+    /// The context has an entry for every link of a found path if it was asked for, unless there is an error in its place.
+    /// The bench is only kept from bench runs.
+    Path(SearchOutcome, #[serde(skip)] ExploredGraph, Vec<LinkContext>, #[serde(skip)] Option<String>, #[serde(skip)] Option<Box<Bench>>),
+    Chain(FirstLinkChain),
+}
+
+/// Forwards the progress of a search to the GUI thread.
+#[cfg(not(target_arch = "wasm32"))]
+struct ChannelObserver {
+    num_of_links_sender: Sender<(usize, usize, usize)>,
+    workers_sender: Sender<Vec<WorkerStatus>>,
+    frontier_sender: Sender<FrontierEvent>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Observer for ChannelObserver {
+    fn progress(&mut self, progress: &Progress) {
+        let _ = self.num_of_links_sender.send((progress.processed, progress.in_queue, progress.depth));
+        let _ = self.workers_sender.send(progress.workers.clone());
+    }
+
+    fn frontier(&mut self, event: &FrontierEvent) {
+        let _ = self.frontier_sender.send(event.clone());
+    }
+}
 
 pub struct SearchingInfo {
     search_from: String,
     search_to: String,
+    constraints: Constraints,
+    mode: SearchMode,
+
+    /// Kept for retrying after an error.
+    config: SearchConfig,
 
     //search_thread: JoinHandle<()>,
-    result_reciever: Receiver<Vec<String>>,
+    /// Error is the message of a panic in the search thread.
+    result_reciever: Receiver<Result<SearchResult, String>>,
 
     num_of_links: Receiver<(usize, usize, usize)>, // (num_of_processed, num_in_queue, search_depth)
     num_of_processed: usize,
@@ -18,33 +99,94 @@ pub struct SearchingInfo {
 
     threads: usize,
 
-    dead_threads_rec: Receiver<usize>,
-    threads_state: Vec<bool>,
+    workers_rec: Receiver<Vec<WorkerStatus>>,
+    workers: Vec<WorkerStatus>,
+
+    frontier_rec: Receiver<FrontierEvent>,
+    tree_view: TreeView,
+    charts: Charts,
 
     start_instant: Instant,
+    cancel_flag: Arc<AtomicBool>,
 }
 
 impl SearchingInfo {
-    pub fn new(from: &str, to: &str, threads: usize) -> Self {
+    /// Bench results are written to `bench_file` unless it is empty.
+    /// The web build searches through the api of the wiki, without workers and bench results.
+    pub fn new(from: &str, to: &str, mode: SearchMode, config: SearchConfig, bench_file: &str) -> Self {
         let (nol_sender, nol_reciever) = mpsc::channel(); // num_of_links
-        let (dt_sender, dt_reciever) = mpsc::channel(); // dead_threads
+        let (workers_sender, workers_reciever) = mpsc::channel();
+        let (frontier_sender, frontier_reciever) = mpsc::channel();
 
         let search_from = from.to_string();
         let search_to = to.to_string();
 
         let sf = from.to_string();
         let st = to.to_string();
+        let constraints = config.constraints.clone();
+        let threads = if mode == SearchMode::FirstLink { 1 } else { config.threads };
 
         let (res_sender, res_reciever) = mpsc::channel();
+        let cancel_flag = Arc::new(AtomicBool::new(false));
 
-        let _thread = thread::spawn(move || {
-            let res = search::search_bench(sf.as_str(), st.as_str(), threads, 0, nol_sender, dt_sender);
-            res_sender.send(res).unwrap();
+        let thread_config = config.clone();
+        let thread_cancel_flag = cancel_flag.clone();
+        let bench_file = bench_file.to_string();
+        #[cfg(not(target_arch = "wasm32"))]
+        thread::spawn(move || {
+            let config = thread_config;
+            let res = panic::catch_unwind(AssertUnwindSafe(|| match mode {
+                SearchMode::ShortestPath => {
+                    let mut builder = Searcher::builder()
+                        .config(config)
+                        .observer(ChannelObserver { num_of_links_sender: nol_sender, workers_sender, frontier_sender })
+                        .watch_frontier(true)
+                        .cancel_flag(thread_cancel_flag);
+                    let bench_run = !bench_file.is_empty();
+                    if bench_run {
+                        builder = builder.bench_file(bench_file);
+                    }
+                    let mut searcher = builder.build();
+                    let report = searcher.run(sf.as_str(), st.as_str());
+                    SearchResult::Path(report.outcome, report.graph, report.context, report.context_error,
+                        if bench_run { Some(Box::new(report.bench)) } else { None })
+                },
+                SearchMode::FirstLink => {
+                    SearchResult::Chain(first_link::follow_first_links(sf.as_str(), st.as_str(), config.wiki_url.as_str(), &config.fetch, 0, nol_sender))
+                },
+            }));
+            // The gui may have moved on
+            let _ = res_sender.send(res.map_err(panic_message));
         });
-        
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = (workers_sender, bench_file);
+            let config = thread_config;
+            let res = match mode {
+                SearchMode::ShortestPath => {
+                    let (outcome, graph) = web::search(sf.as_str(), st.as_str(), &config, &nol_sender, &frontier_sender, &thread_cancel_flag).await;
+                    let (context, context_error) = match &outcome {
+                        SearchOutcome::Found(path) if config.link_context => match web::path_context(path, &config).await {
+                            Ok(context) => (context, None),
+                            Err(e) => (Vec::new(), Some(e)),
+                        },
+                        _ => (Vec::new(), None),
+                    };
+                    SearchResult::Path(outcome, graph, context, context_error, None)
+                },
+                SearchMode::FirstLink => {
+                    SearchResult::Chain(web::follow_first_links(sf.as_str(), st.as_str(), config.wiki_url.as_str(), 0, nol_sender).await)
+                },
+            };
+            let _ = res_sender.send(Ok(res));
+        });
+
         Self {
             search_from,
             search_to,
+            constraints,
+            mode,
+            config,
             //search_thread: thread,
             result_reciever: res_reciever,
             num_of_links: nol_reciever,
@@ -52,9 +194,78 @@ impl SearchingInfo {
             num_in_queue: 0,
             search_depth: 0,
             threads,
-            dead_threads_rec: dt_reciever,
-            threads_state: vec![true; threads],
+            workers_rec: workers_reciever,
+            workers: Vec::new(),
+            frontier_rec: frontier_reciever,
+            tree_view: TreeView::default(),
+            charts: Charts::default(),
             start_instant: Instant::now(),
+            cancel_flag,
+        }
+    }
+
+    /// Takes in what the search thread sent since the last frame.
+    fn poll(&mut self) {
+        // Closed when the search is over, the result comes next
+        while let Ok((pl, ql, dp)) = self.num_of_links.try_recv() {
+            self.num_of_processed = pl;
+            self.num_in_queue = ql;
+            self.search_depth = dp;
+        }
+        if let Some(workers) = self.workers_rec.try_iter().last() {
+            self.workers = workers;
+        }
+        for event in self.frontier_rec.try_iter() {
+            if let FrontierEvent::Discovered { depth, .. } = &event {
+                self.charts.discovered(*depth);
+            }
+            self.tree_view.add(event);
+        }
+        self.charts.sample(self.start_instant.elapsed(), self.num_of_processed, self.num_in_queue, &self.workers);
+    }
+}
+
+/// Stops the search when its tab is closed. A first link chain stops by itself once the receivers are gone.
+impl Drop for SearchingInfo {
+    fn drop(&mut self) {
+        self.cancel_flag.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(payload) => payload.downcast_ref::<&str>().map_or("unknown panic".to_string(), |msg| msg.to_string()),
+    }
+}
+
+/// A search that failed, with what it did before.
+pub struct ErrorInfo {
+    search_from: String,
+    search_to: String,
+    mode: SearchMode,
+    config: SearchConfig,
+    message: String,
+
+    num_of_processed: usize,
+    search_depth: usize,
+    dead_threads: usize,
+    duration: Duration,
+}
+
+impl ErrorInfo {
+    pub fn new(searching_info: &SearchingInfo, message: String) -> Self {
+        Self {
+            search_from: searching_info.search_from.clone(),
+            search_to: searching_info.search_to.clone(),
+            mode: searching_info.mode,
+            config: searching_info.config.clone(),
+            message,
+            num_of_processed: searching_info.num_of_processed,
+            search_depth: searching_info.search_depth,
+            dead_threads: searching_info.workers.iter().filter(|w| !w.alive).count(),
+            duration: searching_info.start_instant.elapsed(),
         }
     }
 }
@@ -62,24 +273,107 @@ impl SearchingInfo {
 pub struct FoundInfo {
     search_from: String,
     search_to: String,
+    constraints: Constraints,
+    /// Wiki the search ran on, the pages of the result link there.
+    wiki_url: String,
     used_threads: usize,
+    /// Limits the search ran with, for running it again.
+    limits: Limits,
     num_of_processed: usize,
     search_depth: usize,
     duration: Duration,
 
-    path: Vec<String>,
+    result: SearchResult,
+    tree_view: TreeView,
+    charts: Charts,
+
+    export_path: String,
+    export_format: GraphFormat,
+    export_status: Option<Result<String, String>>,
 }
 
 impl FoundInfo {
-    pub fn new(searching_info: &SearchingInfo, path: Vec<String>) -> Self {
+    pub fn new(searching_info: &mut SearchingInfo, result: SearchResult) -> Self {
+        let mut tree_view = std::mem::take(&mut searching_info.tree_view);
+        let mut charts = std::mem::take(&mut searching_info.charts);
+        for event in searching_info.frontier_rec.try_iter() {
+            if let FrontierEvent::Discovered { depth, .. } = &event {
+                charts.discovered(*depth);
+            }
+            tree_view.add(event);
+        }
+        if let SearchResult::Path(outcome, ..) = &result {
+            tree_view.set_path(outcome.path());
+        }
+
         Self {
             search_from: searching_info.search_from.clone(),
             search_to: searching_info.search_to.clone(),
+            constraints: searching_info.constraints.clone(),
+            wiki_url: searching_info.config.wiki_url.clone(),
             used_threads: searching_info.threads,
+            limits: searching_info.config.limits.clone(),
             num_of_processed: searching_info.num_of_processed,
             search_depth: searching_info.search_depth,
             duration: searching_info.start_instant.elapsed(),
-            path,
+            result,
+            tree_view,
+            charts,
+            export_path: "graph.dot".to_string(),
+            export_format: GraphFormat::Dot,
+            export_status: None,
+        }
+    }
+
+    fn from_history(entry: &HistoryEntry) -> Self {
+        let mut tree_view = TreeView::default();
+        if let SearchResult::Path(outcome, ..) = &entry.result {
+            tree_view.set_path(outcome.path());
+        }
+
+        Self {
+            search_from: entry.from.clone(),
+            search_to: entry.to.clone(),
+            constraints: entry.constraints.clone(),
+            wiki_url: entry.wiki_url.clone(),
+            used_threads: entry.threads,
+            limits: entry.limits.clone(),
+            num_of_processed: entry.pages_processed,
+            search_depth: entry.depth,
+            duration: Duration::from_secs_f64(entry.duration_secs),
+            result: entry.result.clone(),
+            tree_view,
+            charts: Charts::default(),
+            export_path: "graph.dot".to_string(),
+            export_format: GraphFormat::Dot,
+            export_status: None,
+        }
+    }
+
+    fn mode(&self) -> SearchMode {
+        match self.result {
+            SearchResult::Path(..) => SearchMode::ShortestPath,
+            SearchResult::Chain(_) => SearchMode::FirstLink,
+        }
+    }
+
+    fn history_entry(&self) -> HistoryEntry {
+        let result = match &self.result {
+            SearchResult::Path(outcome, _, context, _, _) => SearchResult::Path(outcome.clone(), ExploredGraph::default(), context.clone(), None, None),
+            chain => chain.clone(),
+        };
+        HistoryEntry {
+            from: self.search_from.clone(),
+            to: self.search_to.clone(),
+            mode: self.mode(),
+            constraints: self.constraints.clone(),
+            wiki_url: self.wiki_url.clone(),
+            threads: self.used_threads,
+            limits: self.limits.clone(),
+            pages_processed: self.num_of_processed,
+            depth: self.search_depth,
+            duration_secs: self.duration.as_secs_f64(),
+            result,
         }
     }
 }
@@ -88,77 +382,601 @@ pub enum State {
     Input,
     Searching(SearchingInfo),       // search thread, num of parsed links, num of links in queue, alive threads
     Found(FoundInfo),               // path from search_from to search_to
+    Error(ErrorInfo),
 }
 
-pub struct TemplateApp {
-    state: State,
+/// What the user typed in the input screen of a tab.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct SearchInputs {
+    wiki_url: String,
     search_from: String,
     search_to: String,
+    search_via: String,
+    search_avoid: String,
+    mode: SearchMode,
     threads: usize,
+    limits: Limits,
+    record_all_links: bool,
 }
 
-pub fn is_valid_wiki_link(url: &str) -> bool {
-    if !url.starts_with("https://en.wikipedia.org/wiki/") {
-        return false;
-    }
-    reqwest::blocking::get(url).is_ok()
-}
-
-impl Default for TemplateApp {
+impl Default for SearchInputs {
     fn default() -> Self {
         Self {
-            state: State::Input,
+            wiki_url: WIKI_URL.to_string(),
             search_from: "https://en.wikipedia.org/wiki/It_Is_the_Law".to_string(),
             //search_to: "https://en.wikipedia.org/wiki/Yale_University".to_string(),
             //search_from: "https://en.wikipedia.org/wiki/Dave_Hollins".to_string(),
             search_to: "https://en.wikipedia.org/wiki/Dab_(dance)".to_string(),
+            search_via: String::new(),
+            search_avoid: String::new(),
+            mode: SearchMode::ShortestPath,
             threads: 1,
+            limits: Limits::default(),
+            record_all_links: false,
+        }
+    }
+}
+
+/// Engine and network settings of all the tabs.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct AdvancedSettings {
+    fetch: FetchOptions,
+    /// Over all the running searches, zero means no limit.
+    max_requests_per_second: f64,
+    /// Cached links are loaded from it at start and saved to it at exit. Empty keeps them in memory only.
+    cache_file: String,
+    /// Empty writes no bench results.
+    bench_file: String,
+    /// Shows the text around every link of a found path.
+    link_context: bool,
+}
+
+impl Default for AdvancedSettings {
+    fn default() -> Self {
+        Self {
+            fetch: FetchOptions::default(),
+            max_requests_per_second: 50.0,
+            cache_file: String::new(),
+            bench_file: "bench.txt".to_string(),
+            link_context: false,
+        }
+    }
+}
+
+/// Kept between sessions. The inputs follow the active tab and fill new tabs.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct Settings {
+    inputs: SearchInputs,
+    advanced: AdvancedSettings,
+    dark_mode: bool,
+    show_history: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            inputs: SearchInputs::default(),
+            advanced: AdvancedSettings::default(),
+            dark_mode: true,
+            show_history: true,
+        }
+    }
+}
+
+/// Ways out of the Found state.
+#[derive(Clone, Copy)]
+enum FoundAction {
+    /// Back to the inputs as they are.
+    NewSearch,
+    /// Back to the inputs, filled with the settings of the shown search.
+    Refine,
+    SearchAgain,
+    /// Searches from the target back to the start.
+    SwapAndSearch,
+}
+
+/// What all the tabs share.
+struct Shared {
+    cache: LinkCache,
+    rate_limiter: RateLimiter,
+    history: History,
+    /// Worker threads that are not used by a running search, updated every frame.
+    free_workers: usize,
+    advanced: AdvancedSettings,
+    /// Result of the last load or save of the cache file.
+    cache_status: Option<Result<String, String>>,
+}
+
+impl Shared {
+    fn set_advanced(&mut self, advanced: AdvancedSettings) {
+        self.rate_limiter = RateLimiter::per_second(advanced.max_requests_per_second);
+        self.advanced = advanced;
+    }
+
+    fn load_cache(&mut self) {
+        let path = self.advanced.cache_file.as_str();
+        self.cache_status = Some(match self.cache.load(Path::new(path)) {
+            Ok(pages) => Ok(format!("Loaded {} pages from {}", pages, path)),
+            Err(e) => Err(format!("Error while reading {}: {}", path, e)),
+        });
+    }
+
+    fn save_cache(&mut self) {
+        let path = self.advanced.cache_file.as_str();
+        self.cache_status = Some(match self.cache.save(Path::new(path)) {
+            Ok(()) => Ok(format!("Saved {} pages to {}", self.cache.len(), path)),
+            Err(e) => Err(format!("Error while writing {}: {}", path, e)),
+        });
+    }
+}
+
+/// One search with its own state machine.
+struct Tab {
+    state: State,
+    /// Result left with a `FoundAction`.
+    previous: Option<FoundInfo>,
+    inputs: SearchInputs,
+    from_input: TitleInput,
+    to_input: TitleInput,
+    /// Search was clicked, the search starts when the pages are checked.
+    search_requested: bool,
+}
+
+pub struct TemplateApp {
+    tabs: Vec<Tab>,
+    active: usize,
+    settings: Settings,
+    shared: Shared,
+}
+
+fn constraints_label(ui: &mut egui::Ui, constraints: &Constraints) {
+    if !constraints.waypoints.is_empty() {
+        ui.label(format!("Via: {}", constraints.waypoints.join(", ")));
+    }
+    if !constraints.excluded.is_empty() {
+        ui.label(format!("Avoiding: {}", constraints.excluded.iter().map(|p| p.as_str()).collect::<Vec<_>>().join(", ")));
+    }
+}
+
+fn workers_table(ui: &mut egui::Ui, workers: &[WorkerStatus]) {
+    let ms = |d: Option<Duration>| d.map_or("-".to_string(), |d| format!("{} ms", d.as_millis()));
+    egui::Grid::new("workers").striped(true).show(ui, |ui| {
+        for header in ["#", "Page", "Done", "Avg fetch", "Last fetch", "Errors", "Retries", "Restarts"] {
+            ui.strong(header);
+        }
+        ui.end_row();
+
+        for (i, worker) in workers.iter().enumerate() {
+            ui.label(i.to_string());
+            match (&worker.page, worker.alive) {
+                (_, false) => { ui.colored_label(egui::Color32::RED, "dead"); },
+                (Some(page), true) => { ui.label(page.as_str()); },
+                (None, true) => { ui.label("idle"); },
+            }
+            ui.label(worker.pages_done.to_string());
+            ui.label(ms(worker.average_fetch()));
+            ui.label(ms(worker.last_fetch));
+            let errors = ui.label(worker.errors.to_string());
+            if let Some(e) = &worker.last_error {
+                errors.on_hover_text(e.as_str());
+            }
+            ui.label(worker.retries.to_string());
+            ui.label(worker.restarts.to_string());
+            ui.end_row();
+        }
+    });
+}
+
+fn link_context_label(ui: &mut egui::Ui, link: &LinkContext) {
+    if link.anchor_text.is_empty() {
+        ui.label(egui::RichText::new("    (link not found on the page)").weak());
+        return;
+    }
+    let section = if link.section.is_empty() { String::new() } else { format!(" in \"{}\"", link.section) };
+    ui.label(egui::RichText::new(format!("    links as \"{}\"{}:", link.anchor_text, section)).weak());
+    if !link.sentence.is_empty() {
+        ui.add(egui::Label::new(egui::RichText::new(format!("    {}", link.sentence)).italics()).wrap(true));
+    }
+}
+
+fn limits_ui(ui: &mut egui::Ui, limits: &mut Limits) {
+    egui::Grid::new("limits").show(ui, |ui| {
+        ui.label("Max depth: ");
+        ui.add(egui::DragValue::new(&mut limits.max_depth).clamp_range(0..=100));
+        ui.end_row();
+
+        ui.label("Max pages processed: ");
+        ui.add(egui::DragValue::new(&mut limits.max_processed).speed(100));
+        ui.end_row();
+
+        ui.label("Max links in queue: ");
+        ui.add(egui::DragValue::new(&mut limits.max_in_queue).speed(1000));
+        ui.end_row();
+
+        ui.label("Max duration (s): ");
+        let mut secs = limits.max_duration.map_or(0, |d| d.as_secs());
+        if ui.add(egui::DragValue::new(&mut secs)).changed() {
+            limits.max_duration = if secs == 0 { None } else { Some(Duration::from_secs(secs)) };
+        }
+        ui.end_row();
+    });
+}
+
+/// Source and limits of the tab, and the settings all the tabs share.
+fn advanced_ui(ui: &mut egui::Ui, inputs: &mut SearchInputs, shortest_path: bool, shared: &mut Shared) {
+    let (mut load_cache, mut save_cache) = (false, false);
+    egui::Grid::new("advanced").show(ui, |ui| {
+        ui.label("Source: ");
+        ui.horizontal(|ui| {
+            let selected = SOURCES.iter().find(|(_, url)| *url == inputs.wiki_url).map_or("Custom", |(name, _)| name);
+            egui::ComboBox::from_id_source("source").selected_text(selected).show_ui(ui, |ui| {
+                for (name, url) in SOURCES {
+                    ui.selectable_value(&mut inputs.wiki_url, url.to_string(), name);
+                }
+            });
+            ui.add(egui::TextEdit::singleline(&mut inputs.wiki_url).hint_text(WIKI_URL));
+        });
+        ui.end_row();
+
+        let advanced = &mut shared.advanced;
+        ui.label("Request timeout (s): ");
+        let mut secs = advanced.fetch.timeout.map_or(0, |d| d.as_secs());
+        if ui.add(egui::DragValue::new(&mut secs)).on_hover_text("0 waits forever").changed() {
+            advanced.fetch.timeout = if secs == 0 { None } else { Some(Duration::from_secs(secs)) };
+        }
+        ui.end_row();
+
+        ui.label("Max requests per second: ");
+        if ui.add(egui::DragValue::new(&mut advanced.max_requests_per_second).clamp_range(0.0..=1000.0))
+            .on_hover_text("Over all the tabs, 0 means no limit").changed() {
+            shared.rate_limiter = RateLimiter::per_second(advanced.max_requests_per_second);
+        }
+        ui.end_row();
+
+        ui.label("User-Agent: ");
+        ui.text_edit_singleline(&mut advanced.fetch.user_agent);
+        ui.end_row();
+
+        ui.label("Proxy: ");
+        ui.add(egui::TextEdit::singleline(&mut advanced.fetch.proxy).hint_text("http://host:port, the system proxy when empty"));
+        ui.end_row();
+
+        ui.label("Cache file: ");
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut advanced.cache_file).hint_text("links are kept in memory only when empty"));
+            let has_file = !advanced.cache_file.is_empty();
+            load_cache = ui.add_enabled(has_file, egui::Button::new("Load")).clicked();
+            save_cache = ui.add_enabled(has_file, egui::Button::new("Save")).clicked();
+        });
+        ui.end_row();
+
+        ui.label("Bench output: ");
+        ui.add(egui::TextEdit::singleline(&mut advanced.bench_file).hint_text("no bench results when empty"))
+            .on_hover_text("The extension picks the format: .txt, .json, .folded for flamegraphs or .trace for chrome://tracing");
+        ui.end_row();
+
+        ui.label("Link context: ");
+        ui.checkbox(&mut advanced.link_context, "Show the text around every link of a found path")
+            .on_hover_text("Keeps a sentence for every page found. Pages that came from the cache are fetched once more");
+        ui.end_row();
+    });
+
+    if load_cache {
+        shared.load_cache();
+    }
+    if save_cache {
+        shared.save_cache();
+    }
+    ui.label(format!("Cached pages: {}", shared.cache.len()));
+    match &shared.cache_status {
+        Some(Ok(msg)) => { ui.label(msg.as_str()); },
+        Some(Err(msg)) => { ui.colored_label(egui::Color32::RED, msg.as_str()); },
+        None => {},
+    }
+
+    ui.add_enabled_ui(shortest_path, |ui| {
+        ui.label("Limits (0 means no limit):");
+        limits_ui(ui, &mut inputs.limits);
+    });
+}
+
+fn export_graph(ui: &mut egui::Ui, graph: &ExploredGraph, path: &mut String, format: &mut GraphFormat, status: &mut Option<Result<String, String>>) {
+    ui.label(format!("Explored graph: {} pages, {} observed links", graph.nodes.len(), graph.links.len()));
+    ui.horizontal(|ui| {
+        for f in GraphFormat::ALL {
+            if ui.radio_value(format, f, f.extension()).changed() {
+                *path = Path::new(path.as_str()).with_extension(f.extension()).to_string_lossy().into_owned();
+            }
+        }
+        ui.text_edit_singleline(path);
+        if ui.button("Export").clicked() {
+            *status = Some(match graph.write(Path::new(path.as_str()), *format) {
+                Ok(()) => Ok(format!("Written to {}", path)),
+                Err(e) => Err(format!("Error while writing {}: {}", path, e)),
+            });
+        }
+    });
+    match status {
+        Some(Ok(msg)) => { ui.label(msg.as_str()); },
+        Some(Err(msg)) => { ui.colored_label(egui::Color32::RED, msg.as_str()); },
+        None => {},
+    }
+}
+
+/// Calls and percentiles of every span, children indented under their parent.
+fn bench_table(ui: &mut egui::Ui, bench: &Bench) {
+    let ms = |d: Duration| format!("{:.1}ms", d.as_secs_f64() * 1000.0);
+    egui::Grid::new("bench").striped(true).show(ui, |ui| {
+        for header in ["Span", "Calls", "Total", "Mean", "p50", "p90", "p99", "Max"] {
+            ui.strong(header);
+        }
+        ui.end_row();
+        for (depth, span) in bench.tree() {
+            ui.label(format!("{}{}", "    ".repeat(depth), span.name));
+            ui.label(span.calls.to_string());
+            ui.label(format!("{:.3}s", span.total.as_secs_f64()));
+            ui.label(ms(span.mean()));
+            ui.label(ms(span.percentile(50.0)));
+            ui.label(ms(span.percentile(90.0)));
+            ui.label(ms(span.percentile(99.0)));
+            ui.label(ms(span.max));
+            ui.end_row();
+        }
+    });
+}
+
+impl Default for TemplateApp {
+    fn default() -> Self {
+        let settings = Settings::default();
+        Self {
+            tabs: vec![Tab::new(settings.inputs.clone())],
+            active: 0,
+            shared: Shared {
+                cache: LinkCache::default(),
+                rate_limiter: RateLimiter::per_second(settings.advanced.max_requests_per_second),
+                history: History::default(),
+                free_workers: MAX_WORKERS,
+                advanced: settings.advanced.clone(),
+                cache_status: None,
+            },
+            settings,
         }
     }
 }
 
-impl TemplateApp {
-    fn input_state(&mut self, _: &egui::CtxRef, ui: &mut egui::Ui) {
+impl Tab {
+    fn new(inputs: SearchInputs) -> Self {
+        Self {
+            state: State::Input,
+            previous: None,
+            inputs,
+            from_input: TitleInput::default(),
+            to_input: TitleInput::default(),
+            search_requested: false,
+        }
+    }
+
+    fn title(&self) -> String {
+        let (from, to) = match &self.state {
+            State::Input => return "New search".to_string(),
+            State::Searching(info) => (info.search_from.as_str(), info.search_to.as_str()),
+            State::Found(info) => (info.search_from.as_str(), info.search_to.as_str()),
+            State::Error(info) => (info.search_from.as_str(), info.search_to.as_str()),
+        };
+        let status = match &self.state {
+            State::Searching(_) => " ...",
+            State::Error(_) => " (failed)",
+            _ => "",
+        };
+        format!("{} → {}{}", title_of(from), title_of(to), status)
+    }
+
+    /// Worker threads used by the running search.
+    fn used_workers(&self) -> usize {
+        match &self.state {
+            State::Searching(info) => info.threads,
+            _ => 0,
+        }
+    }
+
+    fn input_state(&mut self, ui: &mut egui::Ui, shared: &mut Shared) {
+        if self.previous.is_some() && ui.button("Back to the last result").clicked() {
+            self.state = State::Found(self.previous.take().unwrap());
+            return;
+        }
+
+        let inputs = &mut self.inputs;
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut inputs.mode, SearchMode::ShortestPath, "Shortest path");
+            ui.radio_value(&mut inputs.mode, SearchMode::FirstLink, "First link chain");
+        });
+        let shortest_path = inputs.mode == SearchMode::ShortestPath;
+
         egui::Grid::new("1").max_col_width(10000f32).show(ui, |ui| {
             ui.label("From: ");
-            ui.add_enabled(true, egui::TextEdit::singleline(&mut self.search_from));
+            self.from_input.ui(ui, &mut inputs.search_from, inputs.wiki_url.as_str(), &shared.cache, &shared.advanced.fetch);
             ui.end_row();
 
             ui.label("To: ");
-            ui.add_enabled(true, egui::TextEdit::singleline(&mut self.search_to));
+            self.to_input.ui(ui, &mut inputs.search_to, inputs.wiki_url.as_str(), &shared.cache, &shared.advanced.fetch);
             ui.end_row();
+
+            ui.label("Via: ");
+            ui.add_enabled(shortest_path, egui::TextEdit::singleline(&mut inputs.search_via).hint_text("pages to visit in order, comma separated, \"quoted\" if they have a comma"));
+            ui.end_row();
+
+            ui.label("Avoid: ");
+            ui.add_enabled(shortest_path, egui::TextEdit::singleline(&mut inputs.search_avoid).hint_text("titles or patterns (*, ?, # for a digit), comma separated, \"quoted\" if they have a comma"));
+            ui.end_row();
+        });
+        ui.add_enabled(shortest_path, egui::Slider::new(&mut inputs.threads, 1..=MAX_WORKERS).text("threads"));
+        if shortest_path && inputs.threads > shared.free_workers && shared.free_workers > 0 {
+            ui.label(format!("Other tabs are searching, only {} of {} threads are free", shared.free_workers, MAX_WORKERS));
+        }
+        ui.add_enabled(shortest_path, egui::Checkbox::new(&mut inputs.record_all_links, "Record every link for the graph export"));
+        egui::CollapsingHeader::new("Advanced settings").show(ui, |ui| advanced_ui(ui, inputs, shortest_path, shared));
+
+        let checking = self.search_requested &&
+            (self.from_input.is_checking(self.inputs.search_from.as_str(), self.inputs.wiki_url.as_str()) || self.to_input.is_checking(self.inputs.search_to.as_str(), self.inputs.wiki_url.as_str()));
+        let can_start = shared.free_workers > 0;
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!checking && can_start, egui::Button::new("Search")).clicked() && self.inputs.threads > 0 && self.inputs.threads <= MAX_WORKERS {
+                self.from_input.check(self.inputs.search_from.as_str(), self.inputs.wiki_url.as_str(), &shared.advanced.fetch);
+                self.to_input.check(self.inputs.search_to.as_str(), self.inputs.wiki_url.as_str(), &shared.advanced.fetch);
+                self.search_requested = true;
+            }
+            if checking {
+                ui.label("Checking the pages...");
+            }
+            else if !can_start {
+                ui.label("All the threads are used by the searches in other tabs");
+            }
         });
-        ui.add(egui::Slider::new(&mut self.threads, 1..=100).text("threads"));
 
-        if ui.button("Search").clicked() {
-            if self.threads > 0 && self.threads <= 100 && 
-                is_valid_wiki_link(self.search_from.as_str()) && is_valid_wiki_link(self.search_to.as_str()) {
-                self.state = State::Searching(SearchingInfo::new(self.search_from.as_str(), self.search_to.as_str(), self.threads));
+        // Starts the search once both pages are known to exist
+        if self.search_requested {
+            let from = self.from_input.checked(self.inputs.search_from.as_str(), self.inputs.wiki_url.as_str()).cloned();
+            let to = self.to_input.checked(self.inputs.search_to.as_str(), self.inputs.wiki_url.as_str()).cloned();
+            match (from, to) {
+                (Some(Ok(from)), Some(Ok(to))) => {
+                    self.search_requested = false;
+                    self.inputs.search_from = self.inputs.wiki_url.clone() + from.as_str();
+                    self.inputs.search_to = self.inputs.wiki_url.clone() + to.as_str();
+                    self.start_search(shared);
+                },
+                (Some(Err(_)), _) | (_, Some(Err(_))) => self.search_requested = false,
+                // Still checking, unless the text changed meanwhile
+                _ => {
+                    self.search_requested = self.from_input.is_checking(self.inputs.search_from.as_str(), self.inputs.wiki_url.as_str()) ||
+                        self.to_input.is_checking(self.inputs.search_to.as_str(), self.inputs.wiki_url.as_str());
+                },
             }
         }
     }
 
-    fn searching_state(&mut self, ctx: &egui::CtxRef, ui: &mut egui::Ui) {
+    /// Starts a search with the inputs, using at most the free worker threads. Does nothing if there are none.
+    fn start_search(&mut self, shared: &mut Shared) {
+        let config = SearchConfig {
+            wiki_url: self.inputs.wiki_url.clone(),
+            threads: self.inputs.threads,
+            limits: self.inputs.limits.clone(),
+            constraints: Constraints::parse(self.inputs.search_via.as_str(), self.inputs.search_avoid.as_str()),
+            cache: shared.cache.clone(),
+            graph_recording: if self.inputs.record_all_links { GraphRecording::AllLinks } else { GraphRecording::Tree },
+            worker_restarts: WORKER_RESTARTS,
+            rate_limiter: shared.rate_limiter.clone(),
+            fetch: shared.advanced.fetch.clone(),
+            archive: ArchiveMode::Off,
+            link_context: shared.advanced.link_context,
+        };
+        let (from, to) = (self.inputs.search_from.clone(), self.inputs.search_to.clone());
+        self.start_with_config(from.as_str(), to.as_str(), self.inputs.mode, config, shared);
+    }
+
+    fn start_with_config(&mut self, from: &str, to: &str, mode: SearchMode, mut config: SearchConfig, shared: &mut Shared) {
+        if shared.free_workers == 0 {
+            return;
+        }
+        config.threads = config.threads.min(shared.free_workers);
+        let info = SearchingInfo::new(from, to, mode, config, shared.advanced.bench_file.as_str());
+        shared.free_workers -= info.threads;
+        self.state = State::Searching(info);
+    }
+
+    fn history_action(&mut self, action: HistoryAction, shared: &mut Shared) {
+        // A running search is left alone
+        let searching = matches!(self.state, State::Searching(_));
+        match action {
+            HistoryAction::Reopen(i) if !searching => {
+                if let Some(entry) = shared.history.get(i) {
+                    let reopened = State::Found(FoundInfo::from_history(entry));
+                    if let State::Found(current) = std::mem::replace(&mut self.state, reopened) {
+                        self.previous = Some(current);
+                    }
+                }
+            },
+            HistoryAction::Rerun(i) if !searching => {
+                if let Some(entry) = shared.history.get(i).cloned() {
+                    self.load_inputs(&entry);
+                    self.start_search(shared);
+                }
+            },
+            HistoryAction::Reopen(_) | HistoryAction::Rerun(_) => {},
+            HistoryAction::Remove(_) | HistoryAction::Clear => shared.history.apply(&action),
+        }
+    }
+
+    /// Fills the inputs with the settings of a finished search.
+    fn load_inputs(&mut self, entry: &HistoryEntry) {
+        self.inputs.search_from = entry.from.clone();
+        self.inputs.search_to = entry.to.clone();
+        self.inputs.search_via = join_list(entry.constraints.waypoints.iter().map(String::as_str));
+        self.inputs.search_avoid = join_list(entry.constraints.excluded.iter().map(|p| p.as_str()));
+        self.inputs.wiki_url = entry.wiki_url.clone();
+        self.inputs.mode = entry.mode;
+        self.inputs.threads = entry.threads;
+        self.inputs.limits = entry.limits.clone();
+    }
+
+    /// Leaves the shown result, which stays reachable from the input screen.
+    fn leave_found(&mut self, action: FoundAction, shared: &mut Shared) {
+        let found = match std::mem::replace(&mut self.state, State::Input) {
+            State::Found(found) => found,
+            other => {
+                self.state = other;
+                return;
+            },
+        };
+
+        let mut settings = found.history_entry();
+        match action {
+            FoundAction::NewSearch => {},
+            FoundAction::Refine => self.load_inputs(&settings),
+            FoundAction::SearchAgain => {
+                self.load_inputs(&settings);
+                self.start_search(shared);
+            },
+            FoundAction::SwapAndSearch => {
+                std::mem::swap(&mut settings.from, &mut settings.to);
+                settings.constraints.waypoints.reverse();
+                self.load_inputs(&settings);
+                self.start_search(shared);
+            },
+        }
+        self.previous = Some(found);
+    }
+
+    /// Runs every frame for every tab, so searches in the background tabs go on and finish.
+    fn poll(&mut self, shared: &mut Shared) {
         if let State::Searching(info) = &mut self.state {
+            info.poll();
             let res_res = info.result_reciever.try_recv();
             match res_res {
-                Ok(res) => {
+                Ok(Ok(SearchResult::Path(SearchOutcome::Error(e), ..))) => {
+                    self.state = State::Error(ErrorInfo::new(info, e));
+                },
+                Ok(Ok(res)) => {
                     // search thread is joined by now
-                    self.state = State::Found(FoundInfo::new(info, res));
-                    return;
+                    let found_info = FoundInfo::new(info, res);
+                    shared.history.push(found_info.history_entry());
+                    self.state = State::Found(found_info);
                 },
-                Err(reason) => {
-                    match reason {
-                        TryRecvError::Empty => {},
-                        TryRecvError::Disconnected => {
-                            panic!("Search thread is dead");
-                        },
-                    }
+                Ok(Err(e)) => {
+                    self.state = State::Error(ErrorInfo::new(info, format!("search thread panicked: {}", e)));
+                },
+                Err(TryRecvError::Empty) => {},
+                Err(TryRecvError::Disconnected) => {
+                    self.state = State::Error(ErrorInfo::new(info, "search thread stopped without a result".to_string()));
                 },
             }
+        }
+    }
 
-            ctx.request_repaint();
-
+    fn searching_state(&mut self, ui: &mut egui::Ui) {
+        if let State::Searching(info) = &mut self.state {
             egui::Grid::new("1").max_col_width(10000f32).show(ui, |ui| {
                 ui.label("From: ");
                 ui.add_enabled(false, egui::TextEdit::singleline(&mut info.search_from));
@@ -168,81 +986,173 @@ impl TemplateApp {
                 ui.add_enabled(false, egui::TextEdit::singleline(&mut info.search_to));
                 ui.end_row();
             });
-            ui.label(format!("{} thread{} used", info.threads.to_string(), if info.threads > 1 {"s are"} else {" is"}));
+            constraints_label(ui, &info.constraints);
+            ui.label(format!("{} thread{} used", info.threads, if info.threads > 1 {"s are"} else {" is"}));
     
-            loop {
-                let nol_res = info.num_of_links.try_recv();
-                match nol_res {
-                    Ok((pl, ql, dp)) => {
-                        info.num_of_processed = pl;
-                        info.num_in_queue = ql;
-                        info.search_depth = dp;
-                    },
-                    Err(reason) => {
-                        match reason {
-                            TryRecvError::Empty => {},
-                            TryRecvError::Disconnected => {
-                                panic!("Search thread is dead");
-                            },
-                        }
-                        break;
-                    },
-                }
+            ui.label(format!("Pages processed: {} ({} per second)", info.num_of_processed, (info.num_of_processed as f32 / info.start_instant.elapsed().as_secs_f32()) as u32));
+            if info.mode == SearchMode::FirstLink {
+                ui.label(format!("Chain length: {}", info.search_depth + 1));
+            }
+            else {
+                ui.label(format!("Pages in queue: {}", info.num_in_queue));
+                ui.label(format!("Search depth level: {}", info.search_depth));
             }
+            ui.label(format!("Elapsed time: {}s", info.start_instant.elapsed().as_secs_f32()));
+
+            egui::CollapsingHeader::new("Charts").default_open(true).show(ui, |ui| info.charts.ui(ui));
+            if !info.workers.is_empty() {
+                egui::CollapsingHeader::new("Workers").default_open(true).show(ui, |ui| workers_table(ui, &info.workers));
+            }
+            if !info.tree_view.is_empty() {
+                egui::CollapsingHeader::new("Search tree").default_open(true).show(ui, |ui| info.tree_view.ui(ui));
+            }
+        }
+    }
+
+    fn found_state(&mut self, ui: &mut egui::Ui, shared: &mut Shared) {
+        let mut action = None;
+        let can_start = shared.free_workers > 0;
+        ui.horizontal(|ui| {
+            if ui.button("New search").clicked() {
+                action = Some(FoundAction::NewSearch);
+            }
+            if ui.add_enabled(can_start, egui::Button::new("Search again")).clicked() {
+                action = Some(FoundAction::SearchAgain);
+            }
+            if ui.add_enabled(can_start, egui::Button::new("Swap and search back")).clicked() {
+                action = Some(FoundAction::SwapAndSearch);
+            }
+            if ui.button("Refine").on_hover_text("Edit the settings of this search").clicked() {
+                action = Some(FoundAction::Refine);
+            }
+            if !can_start {
+                ui.label("All the threads are used by the searches in other tabs");
+            }
+        });
+        if let Some(action) = action {
+            self.leave_found(action, shared);
+            return;
+        }
+        ui.separator();
+
+        if let State::Found(info) = &mut self.state {
+            egui::Grid::new("1").max_col_width(10000f32).show(ui, |ui| {
+                ui.label("From: ");
+                ui.add_enabled(false, egui::TextEdit::singleline(&mut info.search_from));
+                ui.end_row();
     
-            ui.label(format!("Pages processed: {} ({} per second)", info.num_of_processed, (info.num_of_processed as f32 / info.start_instant.elapsed().as_secs_f32()) as u32));
-            ui.label(format!("Pages in queue: {}", info.num_in_queue));
-            ui.label(format!("Search depth level: {}", info.search_depth));
-            ui.label(format!("Elapsed time: {}s", info.start_instant.elapsed().as_secs_f32().to_string()));
+                ui.label("To: ");
+                ui.add_enabled(false, egui::TextEdit::singleline(&mut info.search_to));
+                ui.end_row();
+            });
+            constraints_label(ui, &info.constraints);
+            ui.label(format!("{} thread{} were used", info.used_threads, if info.used_threads > 1 {"s"} else {""}));
 
-            let dt_res = info.dead_threads_rec.try_recv();
-            match dt_res {
-                Ok(dead_thread_ind) => {
-                    info.threads_state[dead_thread_ind] = !info.threads_state[dead_thread_ind];
+            ui.label(format!("Pages processed: {} ({} per second)", info.num_of_processed, (info.num_of_processed as f32 / info.duration.as_secs_f32()) as u32));
+            ui.label(format!("Search depth level: {}", info.search_depth));
+            ui.label(format!("Elapsed time: {}s", info.duration.as_secs_f32()));
+    
+            match &info.result {
+                SearchResult::Path(SearchOutcome::Found(path), _, context, context_error, _) => {
+                    if let Some(e) = context_error {
+                        ui.colored_label(egui::Color32::RED, format!("No link context: {}", e));
+                    }
+                    ui.label("Path:");
+                    for (i, s) in path.iter().enumerate() {
+                        ui.hyperlink_to(s, info.wiki_url.clone() + s);
+                        if let Some(link) = context.get(i) {
+                            link_context_label(ui, link);
+                        }
+                    }
                 },
-                Err(reason) => {
-                    match reason {
-                        TryRecvError::Empty => {},
-                        TryRecvError::Disconnected => {
-                            panic!("Search thread is dead");
-                        },
+                SearchResult::Path(outcome, ..) => {
+                    ui.colored_label(egui::Color32::RED, outcome.to_string());
+                },
+                SearchResult::Chain(FirstLinkChain { chain, end }) => {
+                    ui.label(match end {
+                        ChainEnd::Target => "Chain reached the target:".to_string(),
+                        ChainEnd::Loop(_) => "Chain ended in a loop:".to_string(),
+                        ChainEnd::DeadEnd => "Chain ended on a page without eligible links:".to_string(),
+                        ChainEnd::StepLimit => "Chain is too long:".to_string(),
+                        ChainEnd::Error(e) => format!("Chain stopped on an error ({}):", e),
+                        ChainEnd::Cancelled => "Chain was cancelled:".to_string(),
+                    });
+                    let loop_start = end.loop_start();
+                    for (i, s) in chain.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{}.", i + 1));
+                            ui.hyperlink_to(s, info.wiki_url.clone() + s);
+                            if loop_start == Some(i) {
+                                ui.colored_label(egui::Color32::YELLOW, "<- loop starts here");
+                            }
+                        });
+                    }
+                    if let Some(i) = loop_start {
+                        ui.label(format!("{} links back to {}. {}", chain.last().unwrap(), i + 1, chain[i]));
                     }
                 },
             }
 
-            for (i, alive) in info.threads_state.iter().enumerate() {
-                if !alive {
-                    ui.label(format!("Thread {} is dead", i));
+            if let SearchResult::Path(_, graph, _, _, bench) = &info.result {
+                if !graph.is_empty() {
+                    ui.separator();
+                    export_graph(ui, graph, &mut info.export_path, &mut info.export_format, &mut info.export_status);
                 }
+                if let Some(bench) = bench.as_deref().filter(|b| !b.is_empty()) {
+                    egui::CollapsingHeader::new("Bench").show(ui, |ui| bench_table(ui, bench));
+                }
+            }
+            if !info.charts.is_empty() {
+                egui::CollapsingHeader::new("Charts").show(ui, |ui| info.charts.ui(ui));
+            }
+            if !info.tree_view.is_empty() {
+                egui::CollapsingHeader::new("Search tree").default_open(true).show(ui, |ui| info.tree_view.ui(ui));
             }
         }
     }
-        
-    fn found_state(&mut self, _: &egui::CtxRef, ui: &mut egui::Ui) {
-        if let State::Found(info) = &mut self.state {
+
+    fn error_state(&mut self, ui: &mut egui::Ui, shared: &mut Shared) {
+        if let State::Error(info) = &mut self.state {
+            ui.heading("Search failed");
+            ui.colored_label(egui::Color32::RED, info.message.as_str());
+
             egui::Grid::new("1").max_col_width(10000f32).show(ui, |ui| {
                 ui.label("From: ");
                 ui.add_enabled(false, egui::TextEdit::singleline(&mut info.search_from));
                 ui.end_row();
-    
+
                 ui.label("To: ");
                 ui.add_enabled(false, egui::TextEdit::singleline(&mut info.search_to));
                 ui.end_row();
             });
-            ui.label(format!("{} thread{} were used", info.used_threads.to_string(), if info.used_threads > 1 {"s"} else {""}));
-
-            ui.label(format!("Pages processed: {} ({} per second)", info.num_of_processed, (info.num_of_processed as f32 / info.duration.as_secs_f32()) as u32));
+            constraints_label(ui, &info.config.constraints);
+            ui.label(format!("Pages processed before the failure: {}", info.num_of_processed));
             ui.label(format!("Search depth level: {}", info.search_depth));
-            ui.label(format!("Elapsed time: {}s", info.duration.as_secs_f32().to_string()));
-    
-            ui.label("Path:");
-            for s in &info.path {
-                ui.hyperlink(s);
+            if info.dead_threads > 0 {
+                ui.label(format!("Dead threads: {}", info.dead_threads));
+            }
+            ui.label(format!("Elapsed time: {}s", info.duration.as_secs_f32()));
+
+            let (mut retry, mut edit) = (false, false);
+            ui.horizontal(|ui| {
+                retry = ui.add_enabled(shared.free_workers > 0, egui::Button::new("Retry")).clicked();
+                edit = ui.button("Edit search").clicked();
+            });
+            if retry {
+                let (from, to, mode, config) = (info.search_from.clone(), info.search_to.clone(), info.mode, info.config.clone());
+                self.start_with_config(from.as_str(), to.as_str(), mode, config, shared);
+            }
+            else if edit {
+                self.state = State::Input;
             }
         }
     }
 }
 
+fn visuals(dark_mode: bool) -> egui::Visuals {
+    if dark_mode { egui::Visuals::dark() } else { egui::Visuals::light() }
+}
+
 impl epi::App for TemplateApp {
     fn name(&self) -> &str {
         "wikisearch"
@@ -253,15 +1163,101 @@ impl epi::App for TemplateApp {
         _ctx: &egui::CtxRef,
         _frame: &epi::Frame,
         _storage: Option<&dyn epi::Storage>,
-    ) {}
+    ) {
+        #[cfg(feature = "persistence")]
+        if let Some(storage) = _storage {
+            self.settings = epi::get_value(storage, SETTINGS_KEY).unwrap_or_default();
+            self.shared.history = epi::get_value(storage, HISTORY_KEY).unwrap_or_default();
+            self.tabs = vec![Tab::new(self.settings.inputs.clone())];
+            self.shared.set_advanced(self.settings.advanced.clone());
+            if !self.settings.advanced.cache_file.is_empty() {
+                self.shared.load_cache();
+            }
+        }
+        _ctx.set_visuals(visuals(self.settings.dark_mode));
+    }
+
+    #[cfg(feature = "persistence")]
+    fn save(&mut self, storage: &mut dyn epi::Storage) {
+        epi::set_value(storage, SETTINGS_KEY, &self.settings);
+        epi::set_value(storage, HISTORY_KEY, &self.shared.history);
+    }
+
+    fn on_exit(&mut self) {
+        if !self.shared.advanced.cache_file.is_empty() {
+            self.shared.save_cache();
+        }
+    }
 
     fn update(&mut self, ctx: &egui::CtxRef, _: &epi::Frame) {
+        self.shared.free_workers = MAX_WORKERS - self.tabs.iter().map(Tab::used_workers).sum::<usize>();
+        for tab in &mut self.tabs {
+            tab.poll(&mut self.shared);
+        }
+        if self.tabs.iter().any(|tab| matches!(tab.state, State::Searching(_))) {
+            ctx.request_repaint();
+        }
+
+        egui::TopBottomPanel::top("menu").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.checkbox(&mut self.settings.dark_mode, "Dark mode").changed() {
+                    ctx.set_visuals(visuals(self.settings.dark_mode));
+                }
+                ui.checkbox(&mut self.settings.show_history, "History");
+                if ui.button("Reset to defaults").on_hover_text("Resets every setting, the history is kept").clicked() {
+                    self.settings = Settings::default();
+                    self.tabs[self.active].inputs = self.settings.inputs.clone();
+                    self.shared.set_advanced(self.settings.advanced.clone());
+                    ctx.set_visuals(visuals(self.settings.dark_mode));
+                }
+            });
+        });
+
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+            let mut close = None;
+            ui.horizontal_wrapped(|ui| {
+                for (i, tab) in self.tabs.iter().enumerate() {
+                    if ui.selectable_label(i == self.active, tab.title()).clicked() {
+                        self.active = i;
+                    }
+                    if self.tabs.len() > 1 && ui.small_button("x").on_hover_text("Close the tab, a running search is stopped").clicked() {
+                        close = Some(i);
+                    }
+                    ui.separator();
+                }
+                if ui.button("+").on_hover_text("New tab").clicked() {
+                    self.tabs.push(Tab::new(self.settings.inputs.clone()));
+                    self.active = self.tabs.len() - 1;
+                }
+            });
+            if let Some(i) = close {
+                self.tabs.remove(i);
+                if self.active > i || self.active == self.tabs.len() {
+                    self.active -= 1;
+                }
+            }
+        });
+
+        let tab = &mut self.tabs[self.active];
+        if self.settings.show_history {
+            let mut history_action = None;
+            egui::SidePanel::left("history").show(ctx, |ui| {
+                history_action = self.shared.history.ui(ui, self.shared.free_workers > 0);
+            });
+            if let Some(action) = history_action {
+                tab.history_action(action, &mut self.shared);
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            match self.state {
-                State::Input => self.input_state(ctx, ui),
-                State::Searching(_) => self.searching_state(ctx, ui),
-                State::Found(_) => self.found_state(ctx, ui),
+            match tab.state {
+                State::Input => tab.input_state(ui, &mut self.shared),
+                State::Searching(_) => tab.searching_state(ui),
+                State::Found(_) => tab.found_state(ui, &mut self.shared),
+                State::Error(_) => tab.error_state(ui, &mut self.shared),
             }
         });
+        self.settings.inputs = tab.inputs.clone();
+        self.settings.advanced = self.shared.advanced.clone();
     }
 }
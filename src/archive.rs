@@ -0,0 +1,96 @@
+//! Links of fetched pages kept in a file, so a search can be run again against the wiki as it was.
+//!
+//! A search with `ArchiveMode::Record` adds every page it gets the links of, fetched or cached, to the archive.
+//! With `ArchiveMode::Replay` it fetches nothing: links only come from the archive, and a page that is not in it has none.
+//! A recording with link context also keeps the context of the links of every fetched page, for the replays.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::context::LinkContext;
+
+/// Links of pages by title, and the context of the links of some of them.
+#[derive(Default, Serialize, Deserialize)]
+struct Pages {
+    links: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    context: BTreeMap<String, Vec<LinkContext>>,
+}
+
+/// Cloning the archive gives another handle to the same pages.
+#[derive(Clone, Default)]
+pub struct Archive {
+    pages: Arc<Mutex<Pages>>,
+}
+
+impl Archive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, title: &str) -> Option<Vec<String>> {
+        self.pages.lock().unwrap().links.get(title).cloned()
+    }
+
+    pub fn insert(&self, title: &str, links: &[String]) {
+        self.pages.lock().unwrap().links.insert(title.to_string(), links.to_vec());
+    }
+
+    /// Context of the links of the page, empty if it was not recorded.
+    pub fn context(&self, title: &str) -> Vec<LinkContext> {
+        self.pages.lock().unwrap().context.get(title).cloned().unwrap_or_default()
+    }
+
+    pub fn insert_context(&self, title: &str, context: &[LinkContext]) {
+        self.pages.lock().unwrap().context.insert(title.to_string(), context.to_vec());
+    }
+
+    /// Writes the pages to `path` as JSON, sorted by title so that archives of the same search can be compared.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let pages = self.pages.lock().unwrap();
+        serde_json::to_writer(BufWriter::new(File::create(path)?), &*pages)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let pages: Pages = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        Ok(Self {
+            pages: Arc::new(Mutex::new(pages)),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.pages.lock().unwrap().links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// What a search does with an archive.
+#[derive(Clone)]
+pub enum ArchiveMode {
+    Off,
+    /// Pages are fetched as usual and added to the archive.
+    Record(Archive),
+    /// Pages are taken from the archive only, without the network and the cache.
+    Replay(Archive),
+}
+
+impl Default for ArchiveMode {
+    fn default() -> Self {
+        ArchiveMode::Off
+    }
+}
+
+impl ArchiveMode {
+    pub fn is_replay(&self) -> bool {
+        matches!(self, ArchiveMode::Replay(_))
+    }
+}
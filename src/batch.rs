@@ -0,0 +1,242 @@
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::context::LinkContext;
+use crate::search::SearchConfig;
+use crate::searcher::{SearchReport, Searcher};
+
+/// Result of one search, as written to reports.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchRecord {
+    pub from: String,
+    pub to: String,
+    pub outcome: String,
+    pub message: String,
+    /// Number of links in the path.
+    pub length: Option<usize>,
+    pub pages_processed: usize,
+    pub duration_secs: f64,
+    pub path: Vec<String>,
+    /// Why every page of the path links to the next one, if the search looked it up.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context: Vec<LinkContext>,
+}
+
+impl SearchRecord {
+    pub fn new(from: &str, to: &str, report: &SearchReport) -> Self {
+        let path = report.outcome.path().to_vec();
+        Self {
+            from: from.to_string(),
+            to: to.to_string(),
+            outcome: report.outcome.kind().to_string(),
+            message: report.outcome.to_string(),
+            length: if path.is_empty() { None } else { Some(path.len() - 1) },
+            pages_processed: report.progress.processed,
+            duration_secs: report.duration.as_secs_f64(),
+            path,
+            context: report.context.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Csv,
+    /// One JSON object per line.
+    Json,
+}
+
+impl ReportFormat {
+    /// Json for ".json" and ".jsonl" files, csv for everything else.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") | Some("jsonl") => ReportFormat::Json,
+            _ => ReportFormat::Csv,
+        }
+    }
+}
+
+const CSV_HEADER: &str = "from,to,outcome,length,pages_processed,duration_secs,path,message";
+/// Separates titles in the path column of csv reports.
+const CSV_PATH_SEPARATOR: &str = " > ";
+
+/// Line breaks become spaces, so that every record is one line and resuming can go line by line.
+fn csv_field(s: &str) -> String {
+    let s = s.replace(|c| c == '\r' || c == '\n', " ");
+    if s.contains(|c| c == ',' || c == '"') {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    }
+    else {
+        s
+    }
+}
+
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                fields.last_mut().unwrap().push('"');
+                chars.next();
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// Reads (from, to) pairs, one per line, separated by a tab or a comma (csv quoting is supported).
+/// Empty lines, lines starting with '#' and a "from,to" header are skipped.
+pub fn read_pairs(path: &Path) -> io::Result<Vec<(String, String)>> {
+    let mut pairs = Vec::new();
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields = if line.contains('\t') {
+            line.split('\t').map(|s| s.to_string()).collect()
+        }
+        else {
+            parse_csv_line(line)
+        };
+        if fields.len() < 2 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("line {}: expected two pages", i + 1)));
+        }
+
+        let (from, to) = (fields[0].trim().to_string(), fields[1].trim().to_string());
+        if pairs.is_empty() && from == "from" && to == "to" {
+            continue;
+        }
+        pairs.push((from, to));
+    }
+    Ok(pairs)
+}
+
+/// Outcomes that say nothing about the pair, so resuming searches it again.
+const RETRIED_OUTCOMES: [&str; 2] = ["error", "cancelled"];
+
+/// Length of the report without a last line that was cut off: records are written with their line break,
+/// so a last line without one is what an interrupted run left.
+fn complete_len(report: &[u8]) -> usize {
+    report.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1)
+}
+
+/// Pairs that already have a record in the report, except for the searches that failed or were cancelled.
+/// A missing report has none.
+pub fn completed_pairs(path: &Path, format: ReportFormat) -> io::Result<HashSet<(String, String)>> {
+    let report = match fs::read(path) {
+        Ok(report) => report,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut res = HashSet::new();
+    for line in report[..complete_len(&report)].lines() {
+        let line = line?;
+        if line.trim().is_empty() || line == CSV_HEADER {
+            continue;
+        }
+        let (from, to, outcome) = match format {
+            ReportFormat::Csv => {
+                let mut fields = parse_csv_line(line.as_str()).into_iter();
+                match (fields.next(), fields.next(), fields.next()) {
+                    (Some(from), Some(to), Some(outcome)) => (from, to, outcome),
+                    _ => continue,
+                }
+            },
+            ReportFormat::Json => match serde_json::from_str::<SearchRecord>(line.as_str()) {
+                Ok(record) => (record.from, record.to, record.outcome),
+                Err(_) => continue,
+            },
+        };
+        if !RETRIED_OUTCOMES.contains(&outcome.as_str()) {
+            res.insert((from, to));
+        }
+    }
+    Ok(res)
+}
+
+pub struct ReportWriter {
+    file: File,
+    format: ReportFormat,
+}
+
+impl ReportWriter {
+    /// Creates the report, or appends to it if `append` is set. Csv reports get a header when they are empty.
+    /// A last line that was cut off by an interrupted run is removed before appending.
+    pub fn open(path: &Path, format: ReportFormat, append: bool) -> io::Result<Self> {
+        let mut file = if append {
+            let mut file = OpenOptions::new().create(true).read(true).append(true).open(path)?;
+            let mut report = Vec::new();
+            file.read_to_end(&mut report)?;
+            if complete_len(&report) < report.len() {
+                file.set_len(complete_len(&report) as u64)?;
+            }
+            file
+        }
+        else {
+            File::create(path)?
+        };
+
+        if format == ReportFormat::Csv && file.metadata()?.len() == 0 {
+            writeln!(file, "{}", CSV_HEADER)?;
+        }
+        Ok(Self { file, format })
+    }
+
+    /// Every record is flushed right away, so an interrupted batch can be resumed.
+    pub fn write(&mut self, record: &SearchRecord) -> io::Result<()> {
+        match self.format {
+            ReportFormat::Csv => {
+                let fields = [
+                    csv_field(record.from.as_str()),
+                    csv_field(record.to.as_str()),
+                    record.outcome.clone(),
+                    record.length.map_or(String::new(), |l| l.to_string()),
+                    record.pages_processed.to_string(),
+                    format!("{:.3}", record.duration_secs),
+                    csv_field(record.path.join(CSV_PATH_SEPARATOR).as_str()),
+                    csv_field(record.message.as_str()),
+                ];
+                writeln!(self.file, "{}", fields.join(","))?;
+            },
+            ReportFormat::Json => {
+                writeln!(self.file, "{}", serde_json::to_string(record)?)?;
+            },
+        }
+        self.file.flush()
+    }
+}
+
+/// Runs a search for every pair that is not in `skip` and writes the results to `report`.
+/// A pair that is retried after an error keeps its old record in the report, the last record of a pair is the one that counts.
+/// All the searches use the same `config`, so they share its link cache.
+/// `on_record` is called with the index of the pair after each search. Returns the number of searches that were run.
+pub fn run_batch<F>(pairs: &[(String, String)], config: &SearchConfig, skip: &HashSet<(String, String)>, report: &mut ReportWriter, mut on_record: F) -> io::Result<usize>
+    where F: FnMut(usize, &SearchRecord) {
+    let mut searcher = Searcher::builder().config(config.clone()).build();
+    let mut num_of_searches = 0usize;
+    for (i, (from, to)) in pairs.iter().enumerate() {
+        if skip.contains(&(from.clone(), to.clone())) {
+            continue;
+        }
+
+        let search_report = searcher.run(from.as_str(), to.as_str());
+        let record = SearchRecord::new(from.as_str(), to.as_str(), &search_report);
+        report.write(&record)?;
+        on_record(i, &record);
+        num_of_searches += 1;
+    }
+    Ok(num_of_searches)
+}
@@ -1,67 +1,437 @@
-use std::{time::{Instant, Duration}};
+//! Time spent in named spans of a search. A span started while another one is running is its child,
+//! so every span is known by its path, like `page/fetch`. Every span keeps a histogram of its calls for the percentiles.
+//!
+//! Results are written as a table, as JSON, as folded stacks for flamegraph tools, or as a Chrome trace
+//! (for chrome://tracing or Perfetto) with a timeline per thread. The trace needs a bench made `with_timeline`.
 
-#[derive(std::fmt::Debug)]
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use instant::Instant;
+use serde::Serialize;
+
+/// Buckets of the histogram below `2 * HALF_BUCKETS` nanoseconds hold one value each, the next ones
+/// get twice as wide every `HALF_BUCKETS` buckets. So every bucket is less than 1/64 of its values wide.
+const HALF_BUCKETS: u64 = 64;
+
+/// Number of calls per duration, HDR style: the precision is relative to the duration, so fast and slow calls fit in the same few kilobytes.
+#[derive(Clone, Debug, Default)]
+struct Histogram {
+    /// Calls per bucket, up to the last used bucket.
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    fn bucket(nanos: u64) -> usize {
+        if nanos < 2 * HALF_BUCKETS {
+            return nanos as usize;
+        }
+        let shift = 64 - nanos.leading_zeros() - (HALF_BUCKETS.trailing_zeros() + 1);
+        (shift as u64 * HALF_BUCKETS + (nanos >> shift)) as usize
+    }
+
+    /// Largest duration that falls into the bucket.
+    fn bucket_max(bucket: usize) -> u64 {
+        let bucket = bucket as u64;
+        if bucket < 2 * HALF_BUCKETS {
+            return bucket;
+        }
+        let shift = bucket / HALF_BUCKETS - 1;
+        let base = bucket - shift * HALF_BUCKETS;
+        (((base as u128 + 1) << shift) - 1).min(u64::MAX as u128) as u64
+    }
+
+    fn record(&mut self, duration: Duration) {
+        let bucket = Self::bucket(duration.as_nanos().min(u64::MAX as u128) as u64);
+        if self.counts.len() <= bucket {
+            self.counts.resize(bucket + 1, 0);
+        }
+        self.counts[bucket] += 1;
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+    }
+
+    /// Smallest bucket maximum that at least `quantile` of the `calls` calls are under.
+    fn quantile(&self, quantile: f64, calls: u64) -> Option<u64> {
+        let rank = ((quantile * calls as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(Self::bucket_max(bucket));
+            }
+        }
+        None
+    }
+}
+
+/// Numbers of one span over all its calls.
+#[derive(Clone, Debug)]
+pub struct Span {
+    pub name: &'static str,
+    /// Index of the enclosing span, `None` at the top level.
+    pub parent: Option<usize>,
+    pub calls: u64,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    histogram: Histogram,
+}
+
+impl Span {
+    fn new(name: &'static str, parent: Option<usize>) -> Self {
+        Self {
+            name,
+            parent,
+            calls: 0,
+            total: Duration::ZERO,
+            min: Duration::ZERO,
+            max: Duration::ZERO,
+            histogram: Histogram::default(),
+        }
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.calls == 0 {
+            Duration::ZERO
+        }
+        else {
+            Duration::from_nanos((self.total.as_nanos() / self.calls as u128) as u64)
+        }
+    }
+
+    /// Duration that `percent` percent of the calls took at most, within 1/64 of it and never above `max`.
+    pub fn percentile(&self, percent: f64) -> Duration {
+        self.histogram.quantile(percent / 100.0, self.calls)
+            .map_or(Duration::ZERO, |nanos| Duration::from_nanos(nanos).min(self.max))
+    }
+
+    fn record(&mut self, elapsed: Duration) {
+        self.min = if self.calls == 0 { elapsed } else { self.min.min(elapsed) };
+        self.max = self.max.max(elapsed);
+        self.calls += 1;
+        self.total += elapsed;
+        self.histogram.record(elapsed);
+    }
+
+    fn merge(&mut self, other: &Span) {
+        if other.calls == 0 {
+            return;
+        }
+        self.min = if self.calls == 0 { other.min } else { self.min.min(other.min) };
+        self.max = self.max.max(other.max);
+        self.calls += other.calls;
+        self.total += other.total;
+        self.histogram.merge(&other.histogram);
+    }
+}
+
+#[derive(Serialize)]
+struct JsonSpan {
+    name: &'static str,
+    calls: u64,
+    /// Seconds.
+    total: f64,
+    min: f64,
+    mean: f64,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    max: f64,
+    children: Vec<JsonSpan>,
+}
+
+/// Ways to write the results of a bench.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BenchFormat {
+    Text,
+    Json,
+    /// One `parent;child microseconds` line per span, with the time spent outside of its children.
+    Folded,
+    /// Chrome trace event JSON, one timeline per thread.
+    Trace,
+}
+
+impl BenchFormat {
+    pub const ALL: [BenchFormat; 4] = [BenchFormat::Text, BenchFormat::Json, BenchFormat::Folded, BenchFormat::Trace];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "txt" => Some(BenchFormat::Text),
+            "json" => Some(BenchFormat::Json),
+            "folded" => Some(BenchFormat::Folded),
+            "trace" => Some(BenchFormat::Trace),
+            _ => None,
+        }
+    }
+
+    /// Guessed from the extension, text if it is unknown.
+    pub fn from_path(path: &Path) -> Self {
+        path.extension().and_then(|e| e.to_str()).and_then(Self::from_name).unwrap_or(BenchFormat::Text)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            BenchFormat::Text => "txt",
+            BenchFormat::Json => "json",
+            BenchFormat::Folded => "folded",
+            BenchFormat::Trace => "trace",
+        }
+    }
+}
+
+/// One call of a span.
+#[derive(Clone, Debug)]
+struct Call {
+    thread: usize,
+    span: usize,
+    start: Instant,
+    duration: Duration,
+}
+
+/// Every call of every thread, in the order they ended on each thread.
+#[derive(Clone, Debug, Default)]
+struct Timeline {
+    thread_names: BTreeMap<usize, String>,
+    calls: Vec<Call>,
+}
+
+#[derive(Serialize)]
+struct TraceEvent {
+    name: String,
+    ph: &'static str,
+    /// Microseconds since the first call.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ts: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dur: Option<f64>,
+    pid: u32,
+    tid: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct Bench {
-    instants: [Instant; 256],
-    durations: [u64; 256],
+    /// Parents come before their children.
+    spans: Vec<Span>,
+    /// Running spans with their start, innermost last.
+    running: Vec<(usize, Instant)>,
+    /// Thread of the calls of this bench, for the timeline.
+    thread: usize,
+    timeline: Option<Timeline>,
 }
 
-#[allow(dead_code)]
 impl Bench {
     pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bench that also keeps every call, as a timeline row of `thread`. It takes memory for every call, so it is off by default.
+    pub fn with_timeline(thread: usize, thread_name: impl Into<String>) -> Self {
+        let mut timeline = Timeline::default();
+        timeline.thread_names.insert(thread, thread_name.into());
         Self {
-            instants: [Instant::now(); 256],
-            durations: [0; 256],
+            thread,
+            timeline: Some(timeline),
+            ..Self::default()
         }
     }
 
-    pub fn combine(&mut self, other: &Bench) {
-        for (i, v) in other.durations.iter().enumerate() {
-            self.durations[i] += v;
+    pub fn has_timeline(&self) -> bool {
+        self.timeline.is_some()
+    }
+
+    /// Index of the span `name` under `parent`, added if it is new.
+    fn child(&mut self, parent: Option<usize>, name: &'static str) -> usize {
+        match self.spans.iter().position(|s| s.parent == parent && s.name == name) {
+            Some(i) => i,
+            None => {
+                self.spans.push(Span::new(name, parent));
+                self.spans.len() - 1
+            },
         }
     }
 
-    pub fn start(&mut self, id: u8) {
-        self.instants[id as usize] = Instant::now();
+    /// Adds the spans of `other`, spans with the same path are merged along with their histograms.
+    /// The timeline of `other` is kept, with its own threads.
+    pub fn combine(&mut self, other: &Bench) {
+        let mut indices = Vec::with_capacity(other.spans.len());
+        for span in &other.spans {
+            let i = self.child(span.parent.map(|p| indices[p]), span.name);
+            self.spans[i].merge(span);
+            indices.push(i);
+        }
+        if let Some(other) = &other.timeline {
+            let timeline = self.timeline.get_or_insert_with(Timeline::default);
+            timeline.thread_names.extend(other.thread_names.iter().map(|(t, name)| (*t, name.clone())));
+            timeline.calls.extend(other.calls.iter().map(|call| Call { span: indices[call.span], ..call.clone() }));
+        }
     }
-    pub fn stop(&mut self, id: u8) {
-        self.durations[id as usize] += self.instants[id as usize].elapsed().as_nanos() as u64;
+
+    /// Starts the span `name` inside the innermost running span.
+    pub fn start(&mut self, name: &'static str) {
+        let parent = self.running.last().map(|&(i, _)| i);
+        let i = self.child(parent, name);
+        self.running.push((i, Instant::now()));
     }
 
-    pub fn call_closure<F>(&mut self, id: u8, f: F) where F: FnOnce() {
-        let start = Instant::now();
-        f();
-        self.durations[id as usize] += start.elapsed().as_nanos() as u64;
+    /// Stops the innermost running span, which has to be `name`.
+    pub fn stop(&mut self, name: &'static str) {
+        let (i, start) = self.running.pop().expect("no running span");
+        debug_assert_eq!(self.spans[i].name, name, "spans have to stop in reverse order");
+        let duration = start.elapsed();
+        self.spans[i].record(duration);
+        if let Some(timeline) = &mut self.timeline {
+            timeline.calls.push(Call { thread: self.thread, span: i, start, duration });
+        }
     }
-    pub fn call_return_closure<F, T>(&mut self, id: u8, f: F) -> T where F: Fn() -> T {
-        let start = Instant::now();
+
+    /// Runs `f` inside the span `name`.
+    pub fn span<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        self.start(name);
         let res = f();
-        self.durations[id as usize] += start.elapsed().as_nanos() as u64;
+        self.stop(name);
         res
     }
-    pub fn call_return_closure_mut<F, T>(&mut self, id: u8, mut f: F) -> T where F: FnMut() -> T {
-        let start = Instant::now();
-        let res = f();
-        self.durations[id as usize] += start.elapsed().as_nanos() as u64;
+
+    /// Names of the span and of its parents, separated by `separator`.
+    fn path(&self, i: usize, separator: &str) -> String {
+        match self.spans[i].parent {
+            Some(parent) => format!("{}{}{}", self.path(parent, separator), separator, self.spans[i].name),
+            None => self.spans[i].name.to_string(),
+        }
+    }
+
+    fn children(&self, parent: Option<usize>) -> impl Iterator<Item = usize> + '_ {
+        (0..self.spans.len()).filter(move |&i| self.spans[i].parent == parent)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Every span followed by its children, with its depth.
+    pub fn tree(&self) -> Vec<(usize, &Span)> {
+        self.tree_indices().into_iter().map(|(i, depth)| (depth, &self.spans[i])).collect()
+    }
+
+    /// Indices of the spans, every span followed by its children, with their depth.
+    fn tree_indices(&self) -> Vec<(usize, usize)> {
+        let mut res = Vec::new();
+        let mut stack: Vec<(usize, usize)> = self.children(None).map(|i| (i, 0)).collect();
+        stack.reverse();
+        while let Some((i, depth)) = stack.pop() {
+            res.push((i, depth));
+            let first = stack.len();
+            stack.extend(self.children(Some(i)).map(|c| (c, depth + 1)));
+            stack[first..].reverse();
+        }
         res
     }
 
-    pub fn get_duration(&self, id: u8) -> Duration {
-        Duration::from_nanos(self.durations[id as usize])
+    /// One line per span, children indented under their parent.
+    pub fn to_text(&self) -> String {
+        let mut res = format!("{:<32} {:>10} {:>14} {:>12} {:>12} {:>12} {:>12} {:>12} {:>12}\n",
+            "span", "calls", "total", "min", "mean", "p50", "p90", "p99", "max");
+        for (depth, span) in self.tree() {
+            res += format!("{:<32} {:>10} {:>13.6}s {:>11.6}s {:>11.6}s {:>11.6}s {:>11.6}s {:>11.6}s {:>11.6}s\n",
+                format!("{}{}", "  ".repeat(depth), span.name), span.calls, span.total.as_secs_f64(),
+                span.min.as_secs_f64(), span.mean().as_secs_f64(), span.percentile(50.0).as_secs_f64(),
+                span.percentile(90.0).as_secs_f64(), span.percentile(99.0).as_secs_f64(), span.max.as_secs_f64()).as_str();
+        }
+        res
     }
 
-    pub fn reset(&mut self, id: u8) {
-        self.durations[id as usize] = 0;
+    fn json_span(&self, i: usize) -> JsonSpan {
+        let span = &self.spans[i];
+        JsonSpan {
+            name: span.name,
+            calls: span.calls,
+            total: span.total.as_secs_f64(),
+            min: span.min.as_secs_f64(),
+            mean: span.mean().as_secs_f64(),
+            p50: span.percentile(50.0).as_secs_f64(),
+            p90: span.percentile(90.0).as_secs_f64(),
+            p99: span.percentile(99.0).as_secs_f64(),
+            max: span.max.as_secs_f64(),
+            children: self.children(Some(i)).map(|c| self.json_span(c)).collect(),
+        }
     }
-}
 
-#[macro_export]
-macro_rules! comine_benches {
-    ( $( $x:expr ),* ) => {
-        {
-            let mut temp_bench = crate::bench::Bench::new();
-            $(temp_bench.combine($x);)*
-            temp_bench
+    /// Top level spans with their children nested in them. Times are in seconds.
+    pub fn to_json(&self) -> String {
+        let spans: Vec<JsonSpan> = self.children(None).map(|i| self.json_span(i)).collect();
+        serde_json::to_string_pretty(&spans).unwrap()
+    }
+
+    /// Time spent in every span outside of its children, in microseconds, for flamegraph tools.
+    /// Spans without time of their own are left out.
+    pub fn to_folded(&self) -> String {
+        let mut res = String::new();
+        for (i, _) in self.tree_indices() {
+            let children: Duration = self.children(Some(i)).map(|c| self.spans[c].total).sum();
+            let own = self.spans[i].total.saturating_sub(children).as_micros();
+            if own > 0 {
+                res += format!("{} {}\n", self.path(i, ";"), own).as_str();
+            }
+        }
+        res
+    }
+
+    /// Chrome trace events: a complete event per call, on the row of its thread. Empty without a timeline.
+    pub fn to_trace(&self) -> String {
+        let mut events = Vec::new();
+        if let Some(timeline) = &self.timeline {
+            for (&thread, name) in &timeline.thread_names {
+                events.push(TraceEvent {
+                    name: "thread_name".to_string(),
+                    ph: "M",
+                    ts: None,
+                    dur: None,
+                    pid: 1,
+                    tid: thread,
+                    args: Some(serde_json::json!({ "name": name })),
+                });
+            }
+            if let Some(epoch) = timeline.calls.iter().map(|c| c.start).min() {
+                for call in &timeline.calls {
+                    events.push(TraceEvent {
+                        name: self.spans[call.span].name.to_string(),
+                        ph: "X",
+                        ts: Some((call.start - epoch).as_secs_f64() * 1e6),
+                        dur: Some(call.duration.as_secs_f64() * 1e6),
+                        pid: 1,
+                        tid: call.thread,
+                        args: Some(serde_json::json!({ "path": self.path(call.span, "/") })),
+                    });
+                }
+            }
+        }
+        serde_json::to_string(&serde_json::json!({ "traceEvents": events, "displayTimeUnit": "ms" })).unwrap()
+    }
+
+    pub fn export(&self, format: BenchFormat) -> String {
+        match format {
+            BenchFormat::Text => self.to_text(),
+            BenchFormat::Json => self.to_json(),
+            BenchFormat::Folded => self.to_folded(),
+            BenchFormat::Trace => self.to_trace(),
         }
-    };
-}
\ No newline at end of file
+    }
+
+    /// In the format guessed from the extension of `path`.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.export(BenchFormat::from_path(path)))
+    }
+}
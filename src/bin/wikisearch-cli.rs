@@ -0,0 +1,387 @@
+#![forbid(unsafe_code)]
+#![cfg_attr(not(debug_assertions), deny(warnings))] // Forbid warnings in release builds
+#![warn(clippy::all, rust_2018_idioms)]
+
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use wikisearch_gui::archive::{Archive, ArchiveMode};
+use wikisearch_gui::batch::{self, ReportFormat, ReportWriter, SearchRecord};
+use wikisearch_gui::cache::LinkCache;
+use wikisearch_gui::constraints::Constraints;
+use wikisearch_gui::graph::{GraphFormat, GraphRecording};
+use wikisearch_gui::rate_limit::RateLimiter;
+use wikisearch_gui::search::{FetchOptions, Limits, SearchConfig, SearchOutcome, WIKI_URL};
+use wikisearch_gui::searcher::{Progress, Searcher};
+
+const USAGE: &str = "\
+Usage: wikisearch-cli [OPTIONS] <FROM> <TO>
+       wikisearch-cli batch [OPTIONS] --report <FILE> <PAIRS>
+
+FROM and TO are page titles or Wikipedia urls.
+PAIRS is a file with one comma (or tab) separated pair of pages per line.
+
+Options:
+    -t, --threads <N>       number of worker threads (default: 4)
+        --restarts <N>      start a worker thread that died again, at most N times (default: 0)
+        --max-rate <N>      send at most N requests per second over all threads
+        --via <PAGES>       comma separated pages the path has to go through, in order (\"quoted\" if they have a comma)
+        --avoid <PATTERNS>  comma separated titles or patterns (*, ?, # for a digit) that are never expanded
+        --max-queue <N>     give up when more than N links are waiting in the queue
+        --max-pages <N>     give up after processing N pages
+        --max-depth <N>     give up when there is no path of N links or less
+        --timeout <SECS>    give up after SECS seconds (per pair in batch mode)
+        --wiki-url <URL>    fetch pages from URL + title (default: https://en.wikipedia.org/wiki/)
+        --request-timeout <SECS>
+                            give up on a single request after SECS seconds, 0 waits forever (default: 30)
+        --proxy <URL>       send every request through the proxy at URL
+        --user-agent <TEXT> User-Agent header of the requests
+        --json              print the result as JSON
+        --context           show the anchor text, section and sentence of every link of the path
+        --export <FILE>     write the explored link graph to FILE
+        --export-format <FORMAT>
+                            dot, graphml or json, guessed from the FILE extension by default
+        --all-links         export every link seen on the processed pages, not just the BFS tree
+        --record <FILE>     save the links of every page the search gets to FILE, for --replay
+        --replay <FILE>     fetch nothing and take the links of every page from FILE, pages missing from it have none
+        --bench <FILE>      write where the search spent its time to FILE, the extension picks the format:
+                            txt, json, folded (flamegraph) or trace (chrome://tracing)
+    -q, --quiet             do not print progress to stderr
+    -h, --help              print this message
+
+Batch options:
+        --report <FILE>     where to write the report
+        --format <FORMAT>   csv or json (one object per line), guessed from the report extension by default
+        --resume            skip the pairs that are already in the report and append to it, failed searches are run again
+        --cache-pages <N>   max number of pages whose links are cached between searches (default: 50000)
+
+Exit codes:
+    0  path found (batch: every pair was searched)
+    1  there is no path
+    2  a limit was hit
+    3  error";
+
+const EXIT_FOUND: i32 = 0;
+const EXIT_NO_PATH: i32 = 1;
+const EXIT_LIMIT: i32 = 2;
+const EXIT_ERROR: i32 = 3;
+
+struct Args {
+    positional: Vec<String>,
+    config: SearchConfig,
+    json: bool,
+    quiet: bool,
+    export: Option<String>,
+    export_format: Option<GraphFormat>,
+    /// Where to save the archive of a recording.
+    record: Option<String>,
+    bench: Option<String>,
+
+    report: Option<String>,
+    format: Option<ReportFormat>,
+    resume: bool,
+}
+
+fn parse_value<T: FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or(format!("Missing value for {}", name))?;
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", name, value))
+}
+
+/// Seconds and rates are finite numbers, 0 or more.
+fn parse_amount(name: &str, value: Option<String>) -> Result<f64, String> {
+    let amount: f64 = parse_value(name, value)?;
+    if !amount.is_finite() || amount < 0.0 {
+        return Err(format!("Invalid value for {}: {}, expected a number, 0 or more", name, amount));
+    }
+    Ok(amount)
+}
+
+/// Returns `Ok(None)` if only the help was requested.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut positional = Vec::new();
+    let mut threads = 4usize;
+    let mut via = String::new();
+    let mut avoid = String::new();
+    let mut limits = Limits::default();
+    let mut wiki_url = WIKI_URL.to_string();
+    let mut fetch = FetchOptions::default();
+    let mut worker_restarts = 0;
+    let mut max_rate = 0.0;
+    let mut json = false;
+    let mut context = false;
+    let mut quiet = false;
+    let mut export = None;
+    let mut export_format = None;
+    let mut all_links = false;
+    let mut record = None;
+    let mut replay: Option<String> = None;
+    let mut bench = None;
+    let mut report = None;
+    let mut format = None;
+    let mut resume = false;
+    let mut cache_pages = 50_000usize;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-t" | "--threads" => threads = parse_value(arg.as_str(), args.next())?,
+            "--restarts" => worker_restarts = parse_value(arg.as_str(), args.next())?,
+            "--max-rate" => max_rate = parse_amount(arg.as_str(), args.next())?,
+            "--via" => via = parse_value(arg.as_str(), args.next())?,
+            "--avoid" => avoid = parse_value(arg.as_str(), args.next())?,
+            "--max-queue" => limits.max_in_queue = parse_value(arg.as_str(), args.next())?,
+            "--max-pages" => limits.max_processed = parse_value(arg.as_str(), args.next())?,
+            "--max-depth" => limits.max_depth = parse_value(arg.as_str(), args.next())?,
+            "--timeout" => limits.max_duration = Some(Duration::from_secs_f64(parse_amount(arg.as_str(), args.next())?)),
+            "--wiki-url" => wiki_url = parse_value(arg.as_str(), args.next())?,
+            "--request-timeout" => {
+                let secs = parse_amount(arg.as_str(), args.next())?;
+                fetch.timeout = if secs > 0.0 { Some(Duration::from_secs_f64(secs)) } else { None };
+            },
+            "--proxy" => fetch.proxy = parse_value(arg.as_str(), args.next())?,
+            "--user-agent" => fetch.user_agent = parse_value(arg.as_str(), args.next())?,
+            "--json" => json = true,
+            "--context" => context = true,
+            "-q" | "--quiet" => quiet = true,
+            "--export" => export = Some(parse_value(arg.as_str(), args.next())?),
+            "--export-format" => {
+                let name: String = parse_value(arg.as_str(), args.next())?;
+                export_format = Some(GraphFormat::from_name(name.as_str()).ok_or(format!("Unknown graph format: {}", name))?);
+            },
+            "--all-links" => all_links = true,
+            "--record" => record = Some(parse_value(arg.as_str(), args.next())?),
+            "--replay" => replay = Some(parse_value(arg.as_str(), args.next())?),
+            "--bench" => bench = Some(parse_value(arg.as_str(), args.next())?),
+            "--report" => report = Some(parse_value(arg.as_str(), args.next())?),
+            "--format" => format = match parse_value::<String>(arg.as_str(), args.next())?.as_str() {
+                "csv" => Some(ReportFormat::Csv),
+                "json" => Some(ReportFormat::Json),
+                f => return Err(format!("Unknown report format: {}", f)),
+            },
+            "--resume" => resume = true,
+            "--cache-pages" => cache_pages = parse_value(arg.as_str(), args.next())?,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ => positional.push(arg),
+        }
+    }
+
+    if positional.first().map(String::as_str) == Some("batch") {
+        if positional.len() != 2 {
+            return Err("Expected exactly one pairs file".to_string());
+        }
+        if report.is_none() {
+            return Err("Batch mode requires --report".to_string());
+        }
+        if export.is_some() {
+            return Err("--export is not supported in batch mode".to_string());
+        }
+        if context {
+            return Err("--context is not supported in batch mode".to_string());
+        }
+        if bench.is_some() {
+            return Err("--bench is not supported in batch mode".to_string());
+        }
+    }
+    else if positional.len() != 2 {
+        return Err("Expected exactly two pages: FROM and TO".to_string());
+    }
+    if threads == 0 {
+        return Err("At least one thread is required".to_string());
+    }
+    let archive = match (&record, &replay) {
+        (Some(_), Some(_)) => return Err("--record and --replay can't be used together".to_string()),
+        (Some(_), None) => ArchiveMode::Record(Archive::new()),
+        (None, Some(path)) => ArchiveMode::Replay(Archive::load(Path::new(path.as_str()))
+            .map_err(|e| format!("Error while reading {}: {}", path, e))?),
+        (None, None) => ArchiveMode::Off,
+    };
+
+    let config = SearchConfig {
+        wiki_url,
+        threads,
+        constraints: Constraints::parse(via.as_str(), avoid.as_str()),
+        limits,
+        // Caching only pays off when several searches are run
+        cache: LinkCache::new(if positional[0] == "batch" { cache_pages } else { 0 }),
+        graph_recording: match (&export, all_links) {
+            (None, _) => GraphRecording::Off,
+            (Some(_), false) => GraphRecording::Tree,
+            (Some(_), true) => GraphRecording::AllLinks,
+        },
+        worker_restarts,
+        rate_limiter: RateLimiter::per_second(max_rate),
+        fetch,
+        archive,
+        link_context: context,
+    };
+    Ok(Some(Args {
+        positional,
+        config,
+        json,
+        quiet,
+        export,
+        export_format,
+        record,
+        bench,
+        report,
+        format,
+        resume,
+    }))
+}
+
+/// Saves the archive of a recording, if there is one. Returns whether that went well.
+fn save_recording(args: &Args) -> bool {
+    if let (Some(path), ArchiveMode::Record(archive)) = (&args.record, &args.config.archive) {
+        match archive.save(Path::new(path.as_str())) {
+            Ok(()) if !args.quiet => eprintln!("Links of {} pages recorded to {}", archive.len(), path),
+            Ok(()) => {},
+            Err(e) => {
+                eprintln!("Error while writing {}: {}", path, e);
+                return false;
+            },
+        }
+    }
+    true
+}
+
+fn run_batch(args: &Args) -> i32 {
+    let pairs_path = Path::new(args.positional[1].as_str());
+    let report_path = Path::new(args.report.as_deref().unwrap());
+    let format = args.format.unwrap_or_else(|| ReportFormat::from_path(report_path));
+
+    let pairs = match batch::read_pairs(pairs_path) {
+        Ok(pairs) => pairs,
+        Err(e) => {
+            eprintln!("Error while reading {}: {}", pairs_path.display(), e);
+            return EXIT_ERROR;
+        },
+    };
+    let skip = if args.resume {
+        match batch::completed_pairs(report_path, format) {
+            Ok(skip) => skip,
+            Err(e) => {
+                eprintln!("Error while reading {}: {}", report_path.display(), e);
+                return EXIT_ERROR;
+            },
+        }
+    }
+    else {
+        Default::default()
+    };
+    let mut report = match ReportWriter::open(report_path, format, args.resume) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error while creating {}: {}", report_path.display(), e);
+            return EXIT_ERROR;
+        },
+    };
+
+    if !args.quiet && !skip.is_empty() {
+        eprintln!("Skipping {} pairs that are already in the report", skip.len());
+    }
+
+    let res = batch::run_batch(&pairs, &args.config, &skip, &mut report, |i, record| {
+        if !args.quiet {
+            eprintln!("[{}/{}] {} -> {}: {} ({} pages processed in {:.1}s)",
+                i + 1, pairs.len(), record.from, record.to, record.message, record.pages_processed, record.duration_secs);
+        }
+    });
+
+    if !save_recording(args) {
+        return EXIT_ERROR;
+    }
+    match res {
+        Ok(_) => EXIT_FOUND,
+        Err(e) => {
+            eprintln!("Error while writing {}: {}", report_path.display(), e);
+            EXIT_ERROR
+        },
+    }
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        },
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(EXIT_ERROR);
+        },
+    };
+
+    if args.positional[0] == "batch" {
+        process::exit(run_batch(&args));
+    }
+    let (from_arg, to_arg) = (args.positional[0].as_str(), args.positional[1].as_str());
+
+    let start_instant = Instant::now();
+    let mut last_print = Instant::now();
+    let quiet = args.quiet;
+    let mut builder = Searcher::builder()
+        .config(args.config.clone())
+        .observer(|p: &Progress| {
+            if !quiet && last_print.elapsed() >= Duration::from_millis(100) {
+                let per_second = p.processed as f32 / start_instant.elapsed().as_secs_f32();
+                eprint!("\rPages processed: {} ({} per second), in queue: {}, depth: {}    ", p.processed, per_second as u32, p.in_queue, p.depth);
+                let _ = std::io::stderr().flush();
+                last_print = Instant::now();
+            }
+        });
+    if let Some(bench) = &args.bench {
+        builder = builder.bench_file(bench.as_str());
+    }
+    let report = builder.run(from_arg, to_arg);
+    if !args.quiet {
+        eprintln!();
+    }
+    let outcome = &report.outcome;
+    let saved = save_recording(&args);
+    if let Some(e) = &report.context_error {
+        eprintln!("No link context: {}", e);
+    }
+
+    let mut exported = true;
+    if let Some(export) = &args.export {
+        let path = Path::new(export.as_str());
+        let format = args.export_format.unwrap_or_else(|| GraphFormat::from_path(path));
+        match report.graph.write(path, format) {
+            Ok(()) if !args.quiet => eprintln!("Explored graph of {} pages written to {}", report.graph.nodes.len(), path.display()),
+            Ok(()) => {},
+            Err(e) => {
+                eprintln!("Error while writing {}: {}", path.display(), e);
+                exported = false;
+            },
+        }
+    }
+
+    if args.json {
+        let record = SearchRecord::new(from_arg, to_arg, &report);
+        println!("{}", serde_json::to_string_pretty(&record).unwrap());
+    }
+    else {
+        for (i, title) in outcome.path().iter().enumerate() {
+            println!("{}", title);
+            if let Some(link) = report.context.get(i) {
+                println!("    -> \"{}\"{}: {}", link.anchor_text,
+                    if link.section.is_empty() { String::new() } else { format!(" in \"{}\"", link.section) }, link.sentence);
+            }
+        }
+        if !args.quiet {
+            eprintln!("{} ({} pages processed in {:.1}s)", outcome, report.progress.processed, report.duration.as_secs_f32());
+        }
+    }
+
+    process::exit(match outcome {
+        _ if !saved || !exported => EXIT_ERROR,
+        SearchOutcome::Found(_) => EXIT_FOUND,
+        SearchOutcome::NoPath => EXIT_NO_PATH,
+        SearchOutcome::LimitReached(_) => EXIT_LIMIT,
+        SearchOutcome::Cancelled | SearchOutcome::Error(_) => EXIT_ERROR,
+    });
+}
@@ -0,0 +1,112 @@
+#![forbid(unsafe_code)]
+#![cfg_attr(not(debug_assertions), deny(warnings))] // Forbid warnings in release builds
+#![warn(clippy::all, rust_2018_idioms)]
+
+use std::process;
+use std::str::FromStr;
+
+use wikisearch_gui::cache::LinkCache;
+use wikisearch_gui::search::{SearchConfig, WIKI_URL};
+use wikisearch_gui::server::{Server, ServerConfig};
+
+const USAGE: &str = "\
+Usage: wikisearch-server [OPTIONS]
+
+Endpoints:
+    POST   /searches              start a search, the body is JSON:
+                                  {\"from\", \"to\", \"threads\", \"via\": [], \"avoid\": [], \"max_pages\", \"max_depth\", \"timeout_secs\"}
+    GET    /searches              status of every search
+    GET    /searches/{id}         status of a search
+    GET    /searches/{id}/events  progress of a search as server-sent events
+    DELETE /searches/{id}         cancel a search
+
+Options:
+        --addr <ADDR>         address to listen on (default: 127.0.0.1:8080)
+        --max-jobs <N>        max number of searches running at the same time (default: 4)
+    -t, --threads <N>         threads per search if the request does not ask for a number (default: 4)
+        --max-threads <N>     max threads a search may ask for (default: 16)
+        --wiki-url <URL>      fetch pages from URL + title (default: https://en.wikipedia.org/wiki/)
+        --cache-pages <N>     max number of pages whose links are cached between searches (default: 50000)
+        --keep-finished <N>   max number of finished searches whose status is kept (default: 1000)
+        --max-connections <N> max number of connections handled at the same time (default: 64)
+    -h, --help                print this message";
+
+fn parse_value<T: FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or(format!("Missing value for {}", name))?;
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", name, value))
+}
+
+/// Returns `Ok(None)` if only the help was requested.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<(String, ServerConfig)>, String> {
+    let mut addr = "127.0.0.1:8080".to_string();
+    let mut max_jobs = 4usize;
+    let mut threads = 4usize;
+    let mut max_threads = 16usize;
+    let mut wiki_url = WIKI_URL.to_string();
+    let mut cache_pages = 50_000usize;
+    let mut max_finished_jobs = 1000usize;
+    let mut max_connections = 64usize;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--addr" => addr = parse_value(arg.as_str(), args.next())?,
+            "--max-jobs" => max_jobs = parse_value(arg.as_str(), args.next())?,
+            "-t" | "--threads" => threads = parse_value(arg.as_str(), args.next())?,
+            "--max-threads" => max_threads = parse_value(arg.as_str(), args.next())?,
+            "--wiki-url" => wiki_url = parse_value(arg.as_str(), args.next())?,
+            "--cache-pages" => cache_pages = parse_value(arg.as_str(), args.next())?,
+            "--keep-finished" => max_finished_jobs = parse_value(arg.as_str(), args.next())?,
+            "--max-connections" => max_connections = parse_value(arg.as_str(), args.next())?,
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+
+    if threads == 0 || max_threads == 0 {
+        return Err("At least one thread is required".to_string());
+    }
+    if max_jobs == 0 {
+        return Err("At least one job is required".to_string());
+    }
+    if max_connections == 0 {
+        return Err("At least one connection is required".to_string());
+    }
+
+    let config = ServerConfig {
+        search: SearchConfig {
+            wiki_url,
+            threads,
+            cache: LinkCache::new(cache_pages),
+            ..Default::default()
+        },
+        max_jobs,
+        max_threads,
+        max_finished_jobs,
+        max_connections,
+    };
+    Ok(Some((addr, config)))
+}
+
+fn main() {
+    let (addr, config) = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        },
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(1);
+        },
+    };
+
+    let server = match Server::bind(addr.as_str(), config) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Error while binding {}: {}", addr, e);
+            process::exit(1);
+        },
+    };
+    eprintln!("Listening on http://{}", server.local_addr().map_or(addr, |a| a.to_string()));
+    server.run();
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Pages `titles_with_prefix` looks at, so that it holds the lock for a bounded time however big the cache is.
+const MAX_SCANNED_PAGES: usize = 5_000;
+
+/// Links of already fetched pages, shared between worker threads and between searches.
+/// Cloning the cache gives another handle to the same storage.
+#[derive(Clone)]
+pub struct LinkCache {
+    links: Arc<Mutex<HashMap<String, Vec<String>>>>,
+    max_pages: usize,
+}
+
+impl Default for LinkCache {
+    fn default() -> Self {
+        Self::new(50_000)
+    }
+}
+
+impl LinkCache {
+    /// Cache that stores links of at most `max_pages` pages. Zero disables caching.
+    pub fn new(max_pages: usize) -> Self {
+        Self {
+            links: Arc::new(Mutex::new(HashMap::new())),
+            max_pages,
+        }
+    }
+
+    pub fn get(&self, title: &str) -> Option<Vec<String>> {
+        self.links.lock().unwrap().get(title).cloned()
+    }
+
+    /// Does nothing once the cache is full.
+    pub fn insert(&self, title: &str, links: &[String]) {
+        let mut map = self.links.lock().unwrap();
+        if map.len() < self.max_pages {
+            map.insert(title.to_string(), links.to_vec());
+        }
+    }
+
+    /// Titles of cached pages and of the pages they link to that start with `prefix`, ignoring case
+    /// and treating spaces as underscores. At most `limit` of them, in alphabetical order.
+    /// Only the first `MAX_SCANNED_PAGES` pages (in no particular order) are looked at.
+    pub fn titles_with_prefix(&self, prefix: &str, limit: usize) -> Vec<String> {
+        let prefix = prefix.replace(' ', "_");
+        // Titles are percent-encoded, comparing ascii letters is enough
+        let matches = |title: &str| title.len() >= prefix.len() && title.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes());
+        let map = self.links.lock().unwrap();
+        let mut titles: Vec<&String> = map.iter()
+            .take(MAX_SCANNED_PAGES)
+            .flat_map(|(title, links)| std::iter::once(title).chain(links))
+            .filter(|title| matches(title))
+            .collect();
+        titles.sort_unstable();
+        titles.dedup();
+        titles.into_iter().take(limit).cloned().collect()
+    }
+
+    /// Writes the links of every cached page to `path` as JSON.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let map = self.links.lock().unwrap();
+        serde_json::to_writer(BufWriter::new(File::create(path)?), &*map)?;
+        Ok(())
+    }
+
+    /// Adds the pages saved to `path` while there is room for them. Returns the number of added pages.
+    pub fn load(&self, path: &Path) -> io::Result<usize> {
+        let saved: HashMap<String, Vec<String>> = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        let mut map = self.links.lock().unwrap();
+        let room = self.max_pages.saturating_sub(map.len());
+        let before = map.len();
+        map.extend(saved.into_iter().take(room));
+        Ok(map.len() - before)
+    }
+
+    pub fn len(&self) -> usize {
+        self.links.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use eframe::egui::{self, plot::{Bar, BarChart, Line, Plot, Value, Values}};
+
+use crate::search::WorkerStatus;
+
+/// Rates are computed over this much time, at first.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+/// Points kept per series. Once there are that many, every other point is dropped and samples are taken half as often,
+/// so the series of a long search stay small and still cover all of it.
+const MAX_POINTS: usize = 512;
+const PLOT_HEIGHT: f32 = 120.0;
+
+/// Time series of a search, sampled while it runs.
+#[derive(Default)]
+pub struct Charts {
+    /// Points are (seconds since the start, value).
+    pages_per_second: Vec<Value>,
+    in_queue: Vec<Value>,
+    /// Percent of the fetches that failed.
+    error_rate: Vec<Value>,
+    discovered_per_depth: Vec<usize>,
+    /// Time, pages processed and errors of the last sample.
+    last_sample: (Duration, usize, usize),
+    /// Times the series were thinned out, the interval between samples doubles every time.
+    thinned: u32,
+}
+
+impl Charts {
+    pub fn is_empty(&self) -> bool {
+        self.pages_per_second.is_empty() && self.discovered_per_depth.is_empty()
+    }
+
+    /// Adds a point to every series, unless the last one is too recent.
+    pub fn sample(&mut self, elapsed: Duration, processed: usize, in_queue: usize, workers: &[WorkerStatus]) {
+        let (last_time, last_processed, last_errors) = self.last_sample;
+        if elapsed < last_time + SAMPLE_INTERVAL * 2u32.pow(self.thinned) {
+            return;
+        }
+        if self.pages_per_second.len() >= MAX_POINTS {
+            for series in [&mut self.pages_per_second, &mut self.in_queue, &mut self.error_rate] {
+                let mut i = 0;
+                series.retain(|_| {
+                    i += 1;
+                    i % 2 == 1
+                });
+            }
+            self.thinned += 1;
+        }
+
+        let errors: usize = workers.iter().map(|w| w.errors).sum();
+        let secs = elapsed.as_secs_f64();
+        let done = processed.saturating_sub(last_processed);
+        let failed = errors.saturating_sub(last_errors);
+        self.pages_per_second.push(Value::new(secs, done as f64 / (elapsed - last_time).as_secs_f64()));
+        self.in_queue.push(Value::new(secs, in_queue as f64));
+        let attempts = done + failed;
+        self.error_rate.push(Value::new(secs, if attempts == 0 { 0.0 } else { failed as f64 * 100.0 / attempts as f64 }));
+        self.last_sample = (elapsed, processed, errors);
+    }
+
+    pub fn discovered(&mut self, depth: usize) {
+        if self.discovered_per_depth.len() <= depth {
+            self.discovered_per_depth.resize(depth + 1, 0);
+        }
+        self.discovered_per_depth[depth] += 1;
+    }
+
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        ui.columns(2, |columns| {
+            time_series(&mut columns[0], "Pages per second", &self.pages_per_second);
+            time_series(&mut columns[1], "Links in queue", &self.in_queue);
+            time_series(&mut columns[0], "Failed fetches (%)", &self.error_rate);
+
+            columns[1].label("Discovered pages per depth");
+            let bars = self.discovered_per_depth.iter().enumerate()
+                .map(|(depth, &n)| Bar::new(depth as f64, n as f64).width(0.6))
+                .collect();
+            Plot::new("Discovered pages per depth").height(PLOT_HEIGHT).include_y(0.0).allow_zoom(false).allow_drag(false)
+                .show(&mut columns[1], |plot| plot.bar_chart(BarChart::new(bars)));
+        });
+    }
+}
+
+fn time_series(ui: &mut egui::Ui, name: &str, values: &[Value]) {
+    ui.label(name);
+    Plot::new(name).height(PLOT_HEIGHT).include_y(0.0).allow_zoom(false).allow_drag(false)
+        .show(ui, |plot| plot.line(Line::new(Values::from_values_iter(values.iter().copied()))));
+}
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+use crate::http::percent_decode;
+
+/// Pattern matched against page titles.
+/// `*` matches any sequence of characters, `?` matches exactly one character and `#` matches one digit.
+/// Spaces are treated as underscores, the same way Wikipedia does in its urls.
+/// Patterns and titles are compared percent-decoded, so "Zürich" matches the link `Z%C3%BCrich` and `?` matches `ü`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TitlePattern(String);
+
+impl TitlePattern {
+    /// Takes a pattern written like a title or a wiki url.
+    pub fn new(pattern: &str) -> Self {
+        Self(percent_decode(title_of(pattern).replace(' ', "_").as_str()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn matches(&self, title: &str) -> bool {
+        let pattern = self.0.chars().collect::<Vec<_>>();
+        let title = percent_decode(title).chars().collect::<Vec<_>>();
+
+        // Classic wildcard matching with backtracking to the last `*`
+        let (mut p, mut t) = (0usize, 0usize);
+        let mut star: Option<(usize, usize)> = None;
+        while t < title.len() {
+            if p < pattern.len() && (pattern[p] == '?' || pattern[p] == title[t] || (pattern[p] == '#' && title[t].is_ascii_digit())) {
+                p += 1;
+                t += 1;
+            }
+            else if p < pattern.len() && pattern[p] == '*' {
+                star = Some((p, t));
+                p += 1;
+            }
+            else if let Some((sp, st)) = star {
+                p = sp + 1;
+                t = st + 1;
+                star = Some((sp, st + 1));
+            }
+            else {
+                return false;
+            }
+        }
+        pattern[p..].iter().all(|c| *c == '*')
+    }
+}
+
+/// Restrictions applied on top of a plain shortest path search.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Constraints {
+    /// Pages the path has to go through, in this order.
+    pub waypoints: Vec<String>,
+    /// Pages that are never expanded (and so never appear inside the path).
+    pub excluded: Vec<TitlePattern>,
+}
+
+/// Takes the page title out of a wiki url: everything after `/wiki/`. Other urls are not guessed at and are returned
+/// as they are, `search::page_title` rejects them. Plain titles are returned as is, slashes included (like "AC/DC").
+pub fn title_of(page: &str) -> &str {
+    let page = page.trim();
+    if !page.starts_with("http://") && !page.starts_with("https://") {
+        return page.strip_prefix("/wiki/").unwrap_or(page);
+    }
+    match page.find("/wiki/") {
+        Some(i) => &page[i + 6..],
+        None => page,
+    }
+}
+
+/// Turns a page title or url into the form Wikipedia uses in its links: underscores instead of spaces and percent-encoded
+/// special characters. Slashes stay as they are, like in the links. Percent-encoded input is decoded first, so it comes
+/// out the same, and a `%` that encodes nothing (like in "100% (album)") is encoded.
+pub fn normalize_title(page: &str) -> String {
+    let title = percent_decode(title_of(page)).replace(' ', "_");
+
+    let mut res = String::with_capacity(title.len());
+    for b in title.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~;@$!*(),:/".contains(&b) {
+            res.push(b as char);
+        }
+        else {
+            res.push_str(format!("%{:02X}", b).as_str());
+        }
+    }
+    res
+}
+
+/// Items of a comma (or newline) separated list. Like in csv, an item in double quotes may contain commas
+/// ("Washington, D.C.") and `""` is a quote inside quotes.
+fn split_list(list: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = list.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                items.last_mut().unwrap().push('"');
+                chars.next();
+            },
+            '"' => quoted = !quoted,
+            ',' | '\n' if !quoted => items.push(String::new()),
+            _ => items.last_mut().unwrap().push(c),
+        }
+    }
+    items.into_iter().map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect()
+}
+
+/// Writes `items` as a comma separated list that `split_list` reads back, quoting the ones that need it.
+pub fn join_list<'a>(items: impl IntoIterator<Item = &'a str>) -> String {
+    items.into_iter().map(|item| {
+        if item.contains(|c| c == ',' || c == '"' || c == '\n') {
+            format!("\"{}\"", item.replace('"', "\"\""))
+        }
+        else {
+            item.to_string()
+        }
+    }).collect::<Vec<_>>().join(", ")
+}
+
+impl Constraints {
+    /// Builds constraints from comma (or newline) separated lists, as typed in the gui. See `split_list` for quoting.
+    pub fn parse(waypoints: &str, excluded: &str) -> Self {
+        Self {
+            waypoints: split_list(waypoints).iter().map(|w| normalize_title(w)).collect(),
+            excluded: split_list(excluded).iter().map(|p| TitlePattern::new(p)).collect(),
+        }
+    }
+
+    pub fn is_excluded(&self, title: &str) -> bool {
+        self.excluded.iter().any(|p| p.matches(title))
+    }
+}
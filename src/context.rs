@@ -0,0 +1,210 @@
+//! Why a page links to the next one: the text around the link.
+//!
+//! Workers look it up in the html they fetch, with the same scan the link extractor uses, and the search keeps it for
+//! the link that discovered every page. Pages that come from the cache have no html, their links are fetched again once
+//! the path is found (at the pace of the search's rate limiter). Replays take it from the archive.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::search;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::search::{get_html, SearchConfig};
+
+/// Where `from` links to `to`. Fields are empty when the link could not be found again.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkContext {
+    pub from: String,
+    pub to: String,
+    /// Text of the link as shown on the page.
+    pub anchor_text: String,
+    /// Heading of the section the link is in, empty in the lead.
+    pub section: String,
+    /// Sentence the link is in, as plain text.
+    pub sentence: String,
+}
+
+/// Context of the first article link to `to` in the html of `from`.
+pub fn link_context(html: &str, from: &str, to: &str) -> Option<LinkContext> {
+    let (tag, _) = search::scan_links(html).into_iter().find(|(_, title)| *title == to)?;
+    Marks::default().context(html, tag, from, to)
+}
+
+/// Context of the first link to every page in the html of `from`, in one pass over the html.
+pub fn page_context(html: &str, from: &str) -> Vec<LinkContext> {
+    let mut marks = Marks::default();
+    let mut seen = HashSet::new();
+    search::scan_links(html).into_iter()
+        .filter(|(_, to)| seen.insert(*to))
+        .filter_map(|(tag, to)| marks.context(html, tag, from, to))
+        .collect()
+}
+
+/// Completes the context of `path` with the links that were not looked up during the search: every page whose link
+/// is missing in `captured` is fetched from `config.wiki_url`, except in a replay, which stays off the network.
+/// Fails on the first page that can not be fetched.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn path_context(path: &[String], captured: Vec<Option<LinkContext>>, config: &SearchConfig) -> Result<Vec<LinkContext>, String> {
+    let mut client = None;
+    path.windows(2).zip(captured.into_iter().chain(std::iter::repeat(None))).map(|(hop, context)| {
+        if let Some(context) = context {
+            return Ok(context);
+        }
+        let missing = LinkContext { from: hop[0].clone(), to: hop[1].clone(), ..Default::default() };
+        if config.archive.is_replay() {
+            return Ok(missing);
+        }
+        if client.is_none() {
+            client = Some(config.fetch.client().map_err(|e| format!("Invalid network settings: {}", e))?);
+        }
+        config.rate_limiter.wait();
+        let html = get_html((config.wiki_url.clone() + hop[0].as_str()).as_str(), client.as_mut().unwrap())
+            .map_err(|e| format!("Error while fetching {}: {}", hop[0], e))?;
+        Ok(link_context(html.as_str(), hop[0].as_str(), hop[1].as_str()).unwrap_or(missing))
+    }).collect()
+}
+
+const BLOCK_STARTS: [&str; 5] = ["<p>", "<p ", "<li>", "<li ", "<td"];
+const BLOCK_ENDS: [&str; 3] = ["</p>", "</li>", "</td>"];
+
+/// Last heading and block (paragraph, list item or cell) start before a link and the next block ends after it.
+/// Links are looked up in order, so every part of the html is searched once however many links the page has.
+#[derive(Default)]
+struct Marks {
+    scanned: usize,
+    heading: Option<(usize, &'static str)>,
+    block_start: Option<usize>,
+    /// Next position of every block end, `None` until it is searched for.
+    block_ends: [Option<usize>; 3],
+}
+
+impl Marks {
+    /// Context of the link whose tag is at `tag`, which is after the links looked up before.
+    fn context(&mut self, html: &str, tag: usize, from: &str, to: &str) -> Option<LinkContext> {
+        let anchor_start = tag + html[tag..].find('>')? + 1;
+        let anchor_end = anchor_start + html[anchor_start..].find("</a>")?;
+        let link_end = anchor_end + 4;
+
+        let chunk = &html[self.scanned..tag];
+        if let Some((i, name)) = ["<h2", "<h3", "<h4"].into_iter().filter_map(|t| chunk.rfind(t).map(|i| (i, &t[1..]))).max_by_key(|(i, _)| *i) {
+            self.heading = Some((self.scanned + i, name));
+        }
+        if let Some(i) = BLOCK_STARTS.iter().filter_map(|t| chunk.rfind(t)).max() {
+            self.block_start = Some(self.scanned + i);
+        }
+        self.scanned = tag;
+        for (end, t) in self.block_ends.iter_mut().zip(BLOCK_ENDS) {
+            if end.map_or(true, |end| end < link_end) {
+                *end = Some(html[link_end..].find(t).map_or(html.len(), |i| link_end + i));
+            }
+        }
+        let block_end = self.block_ends.iter().flatten().copied().min().filter(|&end| end < html.len()).unwrap_or(link_end);
+
+        Some(LinkContext {
+            from: from.to_string(),
+            to: to.to_string(),
+            anchor_text: plain_text(&html[anchor_start..anchor_end]),
+            section: self.heading.map_or_else(String::new, |(start, name)| heading_text(html, start, name, tag)),
+            sentence: sentence_around(html, self.block_start.unwrap_or(tag), tag, link_end, block_end),
+        })
+    }
+}
+
+/// Text of the heading `name` that starts at `start`, before `pos`.
+fn heading_text(html: &str, start: usize, name: &str, pos: usize) -> String {
+    let end = html[start..].find(format!("</{}>", name).as_str()).map_or(pos, |i| start + i);
+    let text = plain_text(&html[start..end]);
+    // Old page layouts put the edit link inside the heading
+    match text.find("[edit") {
+        Some(i) => text[..i].trim().to_string(),
+        None => text,
+    }
+}
+
+/// Sentence of the block from `block_start` to `block_end` around the link from `tag` to `link_end`.
+fn sentence_around(html: &str, block_start: usize, tag: usize, link_end: usize, block_end: usize) -> String {
+    // Markers around the link, so that the spaces next to it survive as in the rest of the text
+    let block = format!("{}\u{1}{}\u{2}{}", &html[block_start..tag], &html[tag..link_end], &html[link_end..block_end]);
+    let text = without_references(plain_text(block.as_str()).as_str());
+    let (link_start, link_end) = match (text.find('\u{1}'), text.find('\u{2}')) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return String::new(),
+    };
+    let (before, link, after) = (&text[..link_start], &text[link_start + 1..link_end], &text[link_end + 1..]);
+
+    let start = before.rfind(". ").map_or(0, |i| i + 2);
+    let end = after.find(". ").map_or(after.len(), |i| i + 1);
+    format!("{}{}{}", &before[start..], link, &after[..end]).trim().to_string()
+}
+
+/// Removes footnote markers like `[12]`.
+fn without_references(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('[') {
+        res.push_str(&rest[..i]);
+        rest = &rest[i..];
+        match rest.find(']') {
+            Some(end) if end > 1 && rest[1..end].bytes().all(|b| b.is_ascii_digit()) => rest = &rest[end + 1..],
+            _ => {
+                res.push('[');
+                rest = &rest[1..];
+            },
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+/// Text of an html fragment: tags dropped, common entities decoded and whitespace collapsed.
+fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {},
+        }
+    }
+    let text = decode_entities(text.as_str());
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('&') {
+        res.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let decoded = rest.find(';').filter(|&end| end <= 8).and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                entity => entity.strip_prefix('#').and_then(|n| match n.strip_prefix('x') {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => n.parse().ok(),
+                }).and_then(char::from_u32),
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                res.push(c);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                res.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    res.push_str(rest);
+    res
+}
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    std::collections::HashMap,
+    std::sync::mpsc::Sender,
+    crate::constraints::normalize_title,
+    crate::search::{get_html, FetchOptions},
+};
+
+/// Why the first link chain stopped.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChainEnd {
+    /// The target page was reached.
+    Target,
+    /// The last page links back to the page with this index in the chain.
+    Loop(usize),
+    /// The last page has no eligible link.
+    DeadEnd,
+    StepLimit,
+    /// The page could not be fetched.
+    Error(String),
+    /// The main thread is closed.
+    Cancelled,
+}
+
+impl ChainEnd {
+    pub fn loop_start(&self) -> Option<usize> {
+        match self {
+            ChainEnd::Loop(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FirstLinkChain {
+    pub chain: Vec<String>,
+    pub end: ChainEnd,
+}
+
+fn tag_name(tag: &str) -> (&str, bool) {
+    let closing = tag.starts_with('/');
+    let tag = tag.trim_start_matches('/');
+    let end = tag.find(|c: char| c.is_whitespace() || c == '/' || c == '>').unwrap_or(tag.len());
+    (&tag[..end], closing)
+}
+
+fn href_title(tag: &str) -> Option<&str> {
+    let beg = tag.find("href=\"/wiki/")? + 12;
+    let x = &tag[beg..];
+    let r = &x[..x.find('"')?];
+    let r = &r[..r.find('#').unwrap_or(r.len())];
+    if r.is_empty() || r.contains(':') {
+        None
+    }
+    else {
+        Some(r)
+    }
+}
+
+/// Finds the first link of the article body that is inside a paragraph and is neither in parentheses nor in italics.
+pub fn first_link(html: &str) -> Option<String> {
+    let beg = html.find("<div id=\"mw-content-text\"")?;
+    let mut x = &html[beg..];
+
+    let mut paragraph_depth = 0usize;
+    let mut italic_depth = 0usize;
+    let mut paren_depth = 0usize;
+
+    while !x.is_empty() {
+        let next = x.find(|c| c == '<' || c == '(' || c == ')').unwrap_or(x.len());
+        x = &x[next..];
+        if x.is_empty() {
+            break;
+        }
+
+        if x.starts_with('(') || x.starts_with(')') {
+            if paragraph_depth > 0 {
+                if x.starts_with('(') {
+                    paren_depth += 1;
+                }
+                else {
+                    paren_depth = paren_depth.saturating_sub(1);
+                }
+            }
+            x = &x[1..];
+            continue;
+        }
+
+        let tag_end = match x.find('>') {
+            Some(tag_end) => tag_end,
+            None => break,
+        };
+        let tag = &x[1..tag_end];
+        x = &x[tag_end + 1..];
+
+        match tag_name(tag) {
+            ("p", false) => {
+                paragraph_depth += 1;
+            },
+            ("p", true) => {
+                paragraph_depth = paragraph_depth.saturating_sub(1);
+                if paragraph_depth == 0 {
+                    paren_depth = 0;
+                    italic_depth = 0;
+                }
+            },
+            ("i", false) | ("em", false) => italic_depth += 1,
+            ("i", true) | ("em", true) => italic_depth = italic_depth.saturating_sub(1),
+            ("a", false) if paragraph_depth > 0 && paren_depth == 0 && italic_depth == 0 => {
+                if let Some(title) = href_title(tag) {
+                    return Some(title.to_string());
+                }
+            },
+            _ => {},
+        }
+    }
+
+    None
+}
+
+/// Follows the first eligible link of every page, starting from `from`, until `to`, a loop or a dead end is reached.
+/// Pages are fetched from `wiki_url` + title, with the client settings of `fetch`.
+/// Sends (num_of_processed, 0, index of the current page) after every fetched page.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn follow_first_links(from: &str, to: &str, wiki_url: &str, fetch: &FetchOptions, max_steps: usize,
+        num_of_links_sender: Sender<(usize, usize, usize)>) -> FirstLinkChain {
+    let to = normalize_title(to);
+    let mut chain = vec![normalize_title(from)];
+    let mut client = match fetch.client() {
+        Ok(client) => client,
+        Err(e) => return FirstLinkChain { chain, end: ChainEnd::Error(format!("Invalid network settings: {}", e)) },
+    };
+    let mut seen = HashMap::new();
+    seen.insert(chain[0].clone(), 0usize);
+
+    loop {
+        let current = chain.last().unwrap().clone();
+        if current == to {
+            return FirstLinkChain { chain, end: ChainEnd::Target };
+        }
+        if max_steps > 0 && chain.len() > max_steps {
+            return FirstLinkChain { chain, end: ChainEnd::StepLimit };
+        }
+
+        let html = match get_html((wiki_url.to_string() + current.as_str()).as_str(), &mut client) {
+            Ok(html) => html,
+            Err(e) => {
+                eprintln!("Error while fetching {}: {}", current, e);
+                return FirstLinkChain { chain, end: ChainEnd::Error(e.to_string()) };
+            },
+        };
+
+        if num_of_links_sender.send((chain.len(), 0, chain.len() - 1)).is_err() {
+            eprintln!("Main thread is closed");
+            return FirstLinkChain { chain, end: ChainEnd::Cancelled };
+        }
+
+        let next = match first_link(html.as_str()) {
+            Some(next) => next,
+            None => return FirstLinkChain { chain, end: ChainEnd::DeadEnd },
+        };
+
+        if let Some(&i) = seen.get(&next) {
+            return FirstLinkChain { chain, end: ChainEnd::Loop(i) };
+        }
+        seen.insert(next.clone(), chain.len());
+        chain.push(next);
+    }
+}
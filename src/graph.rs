@@ -0,0 +1,227 @@
+//! The part of the link graph a search explored, and its export to Graphviz DOT, GraphML and JSON.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+/// How much of the explored graph a search keeps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphRecording {
+    Off,
+    /// Every discovered page with the page it was discovered from (the BFS tree).
+    Tree,
+    /// The tree and every link seen on the processed pages, including links to pages that were already discovered.
+    AllLinks,
+}
+
+impl Default for GraphRecording {
+    fn default() -> Self {
+        GraphRecording::Off
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GraphNode {
+    pub title: String,
+    /// Index of the leg the page was first discovered in, legs are separated by the waypoints.
+    /// A page seen again in a later leg keeps the leg, depth and parent of its first discovery.
+    pub leg: usize,
+    /// Number of links from the start of the leg.
+    pub depth: usize,
+    /// Page the title was discovered from. `None` for the start of a leg.
+    pub parent: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ExploredGraph {
+    pub nodes: Vec<GraphNode>,
+    /// (from, to) links seen on processed pages. Only recorded with `GraphRecording::AllLinks`.
+    pub links: Vec<(String, String)>,
+    /// Path found by the search, if any.
+    pub path: Vec<String>,
+    #[serde(skip)]
+    titles: HashSet<String>,
+}
+
+#[derive(Serialize)]
+struct JsonEdge<'a> {
+    from: &'a str,
+    to: &'a str,
+    /// "tree" for parent links, "link" for the other observed links.
+    kind: &'static str,
+}
+
+#[derive(Serialize)]
+struct JsonGraph<'a> {
+    nodes: &'a [GraphNode],
+    edges: Vec<JsonEdge<'a>>,
+    path: &'a [String],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+    Json,
+}
+
+impl GraphFormat {
+    pub const ALL: [GraphFormat; 3] = [GraphFormat::Dot, GraphFormat::GraphMl, GraphFormat::Json];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dot" | "gv" => Some(GraphFormat::Dot),
+            "graphml" => Some(GraphFormat::GraphMl),
+            "json" => Some(GraphFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// Guessed from the extension, DOT if it is unknown.
+    pub fn from_path(path: &Path) -> Self {
+        path.extension().and_then(|e| e.to_str()).and_then(Self::from_name).unwrap_or(GraphFormat::Dot)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "dot",
+            GraphFormat::GraphMl => "graphml",
+            GraphFormat::Json => "json",
+        }
+    }
+}
+
+fn dot_id(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+impl ExploredGraph {
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Pages that are already in the graph (from an earlier leg) are not added again, so only their first leg is kept.
+    pub(crate) fn add_node(&mut self, title: &str, leg: usize, depth: usize, parent: Option<&str>) {
+        if self.titles.insert(title.to_string()) {
+            self.nodes.push(GraphNode {
+                title: title.to_string(),
+                leg,
+                depth,
+                parent: parent.map(str::to_string),
+            });
+        }
+    }
+
+    pub(crate) fn add_link(&mut self, from: &str, to: &str) {
+        self.links.push((from.to_string(), to.to_string()));
+    }
+
+    /// Parent links first, then the observed links that are not parent links.
+    fn edges(&self) -> Vec<JsonEdge<'_>> {
+        let tree: HashSet<(&str, &str)> = self.nodes.iter()
+            .filter_map(|n| n.parent.as_deref().map(|p| (p, n.title.as_str())))
+            .collect();
+
+        let mut edges: Vec<JsonEdge<'_>> = self.nodes.iter()
+            .filter_map(|n| n.parent.as_deref().map(|p| JsonEdge { from: p, to: n.title.as_str(), kind: "tree" }))
+            .collect();
+        let mut seen = HashSet::new();
+        for (from, to) in &self.links {
+            let key = (from.as_str(), to.as_str());
+            if !tree.contains(&key) && seen.insert(key) {
+                edges.push(JsonEdge { from, to, kind: "link" });
+            }
+        }
+        edges
+    }
+
+    fn path_edges(&self) -> HashSet<(&str, &str)> {
+        self.path.windows(2).map(|p| (p[0].as_str(), p[1].as_str())).collect()
+    }
+
+    /// Path pages and links are drawn in red, observed links that are not parent links are dashed.
+    /// Depth and leg go in the standard `comment` attribute, Graphviz warns about unknown ones.
+    pub fn to_dot(&self) -> String {
+        let on_path: HashSet<&str> = self.path.iter().map(String::as_str).collect();
+        let path_edges = self.path_edges();
+
+        let mut res = String::from("digraph wikisearch {\n    node [shape=box];\n");
+        for node in &self.nodes {
+            res += format!("    {} [label={}, comment=\"depth={}, leg={}\"{}];\n", dot_id(node.title.as_str()), dot_id(node.title.as_str()), node.depth, node.leg,
+                if on_path.contains(node.title.as_str()) { ", color=red" } else { "" }).as_str();
+        }
+        for edge in self.edges() {
+            let mut attrs = vec![];
+            if path_edges.contains(&(edge.from, edge.to)) {
+                attrs.push("color=red");
+            }
+            if edge.kind == "link" {
+                attrs.push("style=dashed");
+            }
+            let attrs = if attrs.is_empty() { String::new() } else { format!(" [{}]", attrs.join(", ")) };
+            res += format!("    {} -> {}{};\n", dot_id(edge.from), dot_id(edge.to), attrs).as_str();
+        }
+        res += "}\n";
+        res
+    }
+
+    /// Pages that are only seen in links (like excluded ones) are declared as nodes without data, GraphML needs every edge end declared.
+    pub fn to_graphml(&self) -> String {
+        let on_path: HashSet<&str> = self.path.iter().map(String::as_str).collect();
+        let path_edges = self.path_edges();
+        let edges = self.edges();
+
+        let mut res = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n\
+            \x20 <key id=\"depth\" for=\"node\" attr.name=\"depth\" attr.type=\"int\"/>\n\
+            \x20 <key id=\"leg\" for=\"node\" attr.name=\"leg\" attr.type=\"int\"/>\n\
+            \x20 <key id=\"node_on_path\" for=\"node\" attr.name=\"on_path\" attr.type=\"boolean\"/>\n\
+            \x20 <key id=\"kind\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>\n\
+            \x20 <key id=\"edge_on_path\" for=\"edge\" attr.name=\"on_path\" attr.type=\"boolean\"/>\n\
+            \x20 <graph id=\"wikisearch\" edgedefault=\"directed\">\n");
+        for node in &self.nodes {
+            res += format!("    <node id=\"{}\"><data key=\"depth\">{}</data><data key=\"leg\">{}</data><data key=\"node_on_path\">{}</data></node>\n",
+                xml_escape(node.title.as_str()), node.depth, node.leg, on_path.contains(node.title.as_str())).as_str();
+        }
+        let mut undeclared = HashSet::new();
+        for title in edges.iter().flat_map(|e| [e.from, e.to]) {
+            if !self.titles.contains(title) && undeclared.insert(title) {
+                res += format!("    <node id=\"{}\"/>\n", xml_escape(title)).as_str();
+            }
+        }
+        for edge in edges {
+            res += format!("    <edge source=\"{}\" target=\"{}\"><data key=\"kind\">{}</data><data key=\"edge_on_path\">{}</data></edge>\n",
+                xml_escape(edge.from), xml_escape(edge.to), edge.kind, path_edges.contains(&(edge.from, edge.to))).as_str();
+        }
+        res += "  </graph>\n</graphml>\n";
+        res
+    }
+
+    pub fn to_json(&self) -> String {
+        let graph = JsonGraph {
+            nodes: &self.nodes,
+            edges: self.edges(),
+            path: &self.path,
+        };
+        serde_json::to_string_pretty(&graph).unwrap()
+    }
+
+    pub fn export(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::GraphMl => self.to_graphml(),
+            GraphFormat::Json => self.to_json(),
+        }
+    }
+
+    pub fn write(&self, path: &Path, format: GraphFormat) -> io::Result<()> {
+        fs::write(path, self.export(format))
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::VecDeque;
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::app::{SearchMode, SearchResult};
+use crate::constraints::{title_of, Constraints};
+use crate::search::{Limits, SearchOutcome, WIKI_URL};
+
+/// Oldest searches are dropped when there are more.
+const MAX_ENTRIES: usize = 200;
+
+/// A finished search, as kept in the history.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub from: String,
+    pub to: String,
+    pub mode: SearchMode,
+    pub constraints: Constraints,
+    /// Wiki the pages were fetched from, Wikipedia for entries saved before it was kept.
+    #[serde(default = "default_wiki_url")]
+    pub wiki_url: String,
+    pub threads: usize,
+    /// No limits for entries saved before they were kept.
+    #[serde(default)]
+    pub limits: Limits,
+    pub pages_processed: usize,
+    pub depth: usize,
+    pub duration_secs: f64,
+    /// The explored graph is not kept.
+    pub result: SearchResult,
+}
+
+fn default_wiki_url() -> String {
+    WIKI_URL.to_string()
+}
+
+impl HistoryEntry {
+    fn summary(&self) -> String {
+        match &self.result {
+            SearchResult::Path(SearchOutcome::Found(path), ..) => format!("{} links", path.len() - 1),
+            SearchResult::Path(outcome, ..) => outcome.kind().replace('_', " "),
+            SearchResult::Chain(chain) => format!("chain of {}", chain.chain.len()),
+        }
+    }
+}
+
+pub enum HistoryAction {
+    /// Show the result again.
+    Reopen(usize),
+    /// Search again with the same settings.
+    Rerun(usize),
+    Remove(usize),
+    Clear,
+}
+
+/// Finished searches, newest first.
+#[derive(Default, Serialize, Deserialize)]
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+}
+
+impl History {
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.entries.push_front(entry);
+        self.entries.truncate(MAX_ENTRIES);
+    }
+
+    pub fn get(&self, i: usize) -> Option<&HistoryEntry> {
+        self.entries.get(i)
+    }
+
+    pub fn apply(&mut self, action: &HistoryAction) {
+        match action {
+            HistoryAction::Remove(i) => {
+                self.entries.remove(*i);
+            },
+            HistoryAction::Clear => self.entries.clear(),
+            HistoryAction::Reopen(_) | HistoryAction::Rerun(_) => {},
+        }
+    }
+
+    /// Without `can_run`, searches can't be run again.
+    pub fn ui(&self, ui: &mut egui::Ui, can_run: bool) -> Option<HistoryAction> {
+        let mut action = None;
+        ui.horizontal(|ui| {
+            ui.heading("History");
+            if !self.entries.is_empty() && ui.small_button("Clear").clicked() {
+                action = Some(HistoryAction::Clear);
+            }
+        });
+        if self.entries.is_empty() {
+            ui.label("Finished searches show up here");
+        }
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (i, entry) in self.entries.iter().enumerate() {
+                ui.separator();
+                ui.label(format!("{} -> {}", title_of(entry.from.as_str()), title_of(entry.to.as_str())));
+                ui.label(format!("{}, {} pages in {:.1}s", entry.summary(), entry.pages_processed, entry.duration_secs));
+                ui.horizontal(|ui| {
+                    if ui.small_button("Open").clicked() {
+                        action = Some(HistoryAction::Reopen(i));
+                    }
+                    if ui.add_enabled(can_run, egui::Button::new("Run again").small()).clicked() {
+                        action = Some(HistoryAction::Rerun(i));
+                    }
+                    if ui.small_button("Remove").clicked() {
+                        action = Some(HistoryAction::Remove(i));
+                    }
+                });
+            }
+        });
+        action
+    }
+}
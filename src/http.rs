@@ -0,0 +1,172 @@
+//! Just enough HTTP/1.1 for the search server and the fake wiki: one request per connection, no keep-alive.
+
+// The web build only decodes titles
+#![cfg_attr(target_arch = "wasm32", allow(dead_code))]
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+/// Longest line of the head of a request, with its line break.
+pub const MAX_LINE_LEN: usize = 8 * 1024;
+/// Max number of header lines of a request.
+pub const MAX_HEADERS: usize = 100;
+
+pub struct Request {
+    pub method: String,
+    /// Path without the query string.
+    pub path: String,
+    /// Raw query string, without the `?`. Only the fake wiki reads it.
+    #[cfg_attr(not(feature = "test-support"), allow(dead_code))]
+    pub query: String,
+    pub body: Vec<u8>,
+}
+
+/// Why a request could not be read.
+#[derive(Debug)]
+pub enum RequestError {
+    /// The body is longer than allowed. It is not read.
+    BodyTooLarge,
+    /// A line of the head is longer than `MAX_LINE_LEN`, or there are more than `MAX_HEADERS` headers.
+    HeadTooLarge,
+    /// Not an HTTP request, with the reason.
+    Malformed(&'static str),
+    /// The connection failed, timed out or was closed before the end of the request. Nobody is left to answer.
+    Io,
+}
+
+impl From<io::Error> for RequestError {
+    fn from(_: io::Error) -> Self {
+        RequestError::Io
+    }
+}
+
+/// Reads a line of the head into `line`, returns its length with the line break. Zero at the end of the stream.
+fn read_head_line(reader: &mut impl BufRead, line: &mut String) -> Result<usize, RequestError> {
+    line.clear();
+    let len = (&mut *reader).take(MAX_LINE_LEN as u64).read_line(line)?;
+    if len == MAX_LINE_LEN && !line.ends_with('\n') {
+        return Err(RequestError::HeadTooLarge);
+    }
+    Ok(len)
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() && hex_value(bytes[i + 1]).is_some() && hex_value(bytes[i + 2]).is_some() => {
+                res.push(hex_value(bytes[i + 1]).unwrap() * 16 + hex_value(bytes[i + 2]).unwrap());
+                i += 3;
+            },
+            b => {
+                res.push(b);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&res).into_owned()
+}
+
+/// Decoded value of `name` in a query string.
+#[cfg_attr(not(feature = "test-support"), allow(dead_code))]
+pub fn query_param(query: &str, name: &str) -> Option<String> {
+    query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| percent_decode(value.replace('+', " ").as_str()))
+}
+
+/// Reads the head line by line, so that a client can't make it buffer more than `MAX_LINE_LEN` at once,
+/// and the body if it is at most `max_body_len` long.
+pub fn read_request(stream: &TcpStream, max_body_len: usize) -> Result<Request, RequestError> {
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    if read_head_line(&mut reader, &mut line)? == 0 {
+        return Err(RequestError::Io);
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or(RequestError::Malformed("empty request"))?.to_string();
+    let target = parts.next().ok_or(RequestError::Malformed("no request target"))?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let (path, query) = (path.to_string(), query.to_string());
+
+    let mut headers = HashMap::new();
+    let mut num_of_headers = 0;
+    loop {
+        if read_head_line(&mut reader, &mut line)? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        num_of_headers += 1;
+        if num_of_headers > MAX_HEADERS {
+            return Err(RequestError::HeadTooLarge);
+        }
+        if let Some(i) = header.find(':') {
+            headers.insert(header[..i].trim().to_lowercase(), header[i + 1..].trim().to_string());
+        }
+    }
+
+    let length: u64 = match headers.get("content-length") {
+        Some(length) => length.parse().map_err(|_| RequestError::Malformed("bad content length"))?,
+        None => 0,
+    };
+    if length > max_body_len as u64 {
+        return Err(RequestError::BodyTooLarge);
+    }
+    let mut body = Vec::with_capacity(length as usize);
+    reader.take(length).read_to_end(&mut body)?;
+    if body.len() as u64 != length {
+        return Err(RequestError::Io);
+    }
+
+    Ok(Request { method, path, query, body })
+}
+
+pub fn status_text(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+/// Writes the status line and the headers. The connection is always closed after the response.
+pub fn write_head(stream: &mut impl Write, status: u16, headers: &[(&str, &str)]) -> io::Result<()> {
+    write!(stream, "HTTP/1.1 {} {}\r\n", status, status_text(status))?;
+    for (name, value) in headers {
+        write!(stream, "{}: {}\r\n", name, value)?;
+    }
+    write!(stream, "Connection: close\r\nAccess-Control-Allow-Origin: *\r\n\r\n")?;
+    stream.flush()
+}
+
+pub fn write_response(stream: &mut impl Write, status: u16, content_type: &str, body: &[u8]) -> io::Result<()> {
+    let length = body.len().to_string();
+    write_head(stream, status, &[("Content-Type", content_type), ("Content-Length", length.as_str())])?;
+    stream.write_all(body)?;
+    stream.flush()
+}
@@ -2,22 +2,68 @@
 #![cfg_attr(not(debug_assertions), deny(warnings))] // Forbid warnings in release builds
 #![warn(clippy::all, rust_2018_idioms)]
 
-mod bench;
-mod search;
+//! Finds the shortest chain of links between two Wikipedia pages.
+//!
+//! [`Searcher`] is the entry point for embedding the engine, the GUI, the CLI and the server are built on it.
+//! The GUI is behind the default `gui` feature, turn default features off to use the crate without eframe.
+//! The web build has no threads and no blocking requests, it searches through the MediaWiki api instead (the `web` module).
+
+pub mod archive;
+pub mod bench;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod batch;
+pub mod cache;
+pub mod constraints;
+pub mod context;
+pub mod first_link;
+pub mod graph;
+mod http;
+pub mod rate_limit;
+pub mod search;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod searcher;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod server;
+pub mod suggest;
+#[cfg(all(feature = "test-support", not(target_arch = "wasm32")))]
+pub mod test_support;
+#[cfg(target_arch = "wasm32")]
+pub mod web;
+#[cfg(feature = "gui")]
 mod app;
+#[cfg(feature = "gui")]
+mod charts;
+#[cfg(feature = "gui")]
+mod history;
+#[cfg(feature = "gui")]
+mod title_input;
+#[cfg(feature = "gui")]
+mod tree_view;
+#[cfg(feature = "gui")]
 pub use app::TemplateApp;
 
+pub use archive::{Archive, ArchiveMode};
+pub use bench::{Bench, BenchFormat};
+pub use cache::LinkCache;
+pub use constraints::Constraints;
+pub use graph::{ExploredGraph, GraphFormat, GraphRecording};
+pub use context::LinkContext;
+pub use rate_limit::RateLimiter;
+pub use search::{FetchOptions, FrontierEvent, Limits, SearchConfig, SearchOutcome, WorkerStatus};
+#[cfg(not(target_arch = "wasm32"))]
+pub use searcher::{Observer, Progress, SearchReport, Searcher, SearcherBuilder};
+
 // ----------------------------------------------------------------------------
 // When compiling for web:
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", feature = "gui"))]
 use eframe::wasm_bindgen::{self, prelude::*};
 
 /// This is the entry-point for all the web-assembly.
 /// This is called once from the HTML.
 /// It loads the app, installs some callbacks, then returns.
 /// You can add more callbacks like this if you want to call in to your code.
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", feature = "gui"))]
 #[wasm_bindgen]
 pub fn start(canvas_id: &str) -> Result<(), eframe::wasm_bindgen::JsValue> {
     let app = TemplateApp::default();
@@ -0,0 +1,59 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use instant::Instant;
+
+/// Spaces out the requests of every search that shares it.
+/// Cloning the limiter gives another handle to the same budget.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    /// When the next request may be sent. `None` means no limit.
+    next: Option<Arc<Mutex<Instant>>>,
+    interval: Duration,
+}
+
+impl RateLimiter {
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// At most `requests` requests per second. Zero means no limit.
+    pub fn per_second(requests: f64) -> Self {
+        if requests <= 0.0 {
+            return Self::unlimited();
+        }
+        Self {
+            next: Some(Arc::new(Mutex::new(Instant::now()))),
+            interval: Duration::from_secs_f64(1.0 / requests),
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.next.is_none()
+    }
+
+    /// Requests per second, zero if there is no limit.
+    pub fn rate(&self) -> f64 {
+        if self.is_unlimited() { 0.0 } else { 1.0 / self.interval.as_secs_f64() }
+    }
+
+    /// Takes the next free slot and returns how long to wait for it.
+    pub fn reserve(&self) -> Duration {
+        match &self.next {
+            Some(next) => {
+                let mut next = next.lock().unwrap();
+                let now = Instant::now();
+                let slot = (*next).max(now);
+                *next = slot + self.interval;
+                slot - now
+            },
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Blocks until the next request may be sent.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn wait(&self) {
+        std::thread::sleep(self.reserve());
+    }
+}
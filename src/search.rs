@@ -1,100 +1,145 @@
-use std::collections::HashMap;
-use std::collections::LinkedList;
-use std::fs::File;
-use std::io::Write;
-use std::mem::swap;
-use std::sync::mpsc::Receiver;
-use std::sync::mpsc::{self, Sender};
-use std::sync::mpsc::TryRecvError;
-use std::thread;
-
-use reqwest::blocking::Client;
-
-use crate::bench::Bench;
-
-macro_rules! starts_with_any {
-    ($s:expr; $($pats:expr),+) => {
-        $($s.starts_with($pats))||+
-    };
-}
-
-fn get_html(from: &str, client: &mut Client) -> Result<String, Box<dyn std::error::Error>> {
-    Ok(client.get(from).send()?.text()?)
-}
-fn get_html_bench(from: &str, client: &mut Client, bench: &mut Bench) -> Result<String, Box<dyn std::error::Error>> {
-    bench.start(0);
-    let r = get_html(from, client);
-    bench.stop(0);
-    r
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::archive::ArchiveMode;
+use crate::cache::LinkCache;
+use crate::constraints::{Constraints, normalize_title};
+use crate::graph::GraphRecording;
+use crate::rate_limit::RateLimiter;
+
+// The threaded engine below can't run in the browser, the web build searches with `crate::web`
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    std::collections::{HashMap, HashSet, LinkedList},
+    std::mem::swap,
+    std::path::Path,
+    std::sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    std::thread::{self, JoinHandle},
+    std::time::Instant,
+    reqwest::blocking::Client,
+    reqwest::StatusCode,
+    crate::bench::{Bench, BenchFormat},
+    crate::context::{self, LinkContext},
+    crate::graph::ExploredGraph,
+    crate::searcher::Progress,
+};
+
+pub const WIKI_URL: &str = "https://en.wikipedia.org/wiki/";
+pub const USER_AGENT: &str = concat!("wikisearch/", env!("CARGO_PKG_VERSION"));
+
+/// Error statuses (like 404 or 429) are errors too, otherwise the error page would be scanned as a page without links
+/// and a rate limited page would silently become a dead end instead of being retried.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn get_html(from: &str, client: &mut Client) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(client.get(from).send()?.error_for_status()?.text()?)
 }
 
-#[allow(dead_code)]
-fn get_links(from: &str, client: &mut Client) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let html = get_html(("https://en.wikipedia.org/wiki/".to_string() + &from).as_str(), client)?;
 
-    let beg = html.find("<div id=\"mw-content-text\"");
-    if let Some(beg) = beg {
-        let mut res = Vec::new();
+/// Why a page typed by the user can't be searched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PageError {
+    Empty,
+    /// A url that is not on the wiki being searched.
+    NotWikiUrl,
+    Missing,
+    /// The wiki could not be asked, with the reason.
+    Network(String),
+}
 
-        let mut x = &html[beg..];
-        
-        while let Some(ind) = x.find("<a href=\"/wiki/") {
-            x = &x[ind + 15..];
-            let ref_end = x.find("\"").unwrap();
-            let r = &x[..ref_end];
-            if starts_with_any!(r; "File:", "Category:", "Special:", "Talk:", "Wikipedia:", "Template:", "Portal:", "Help:") {
-                res.push(r.to_string());
-            }
-            x = &x[ref_end..];
+impl fmt::Display for PageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PageError::Empty => write!(f, "no page given"),
+            PageError::NotWikiUrl => write!(f, "not a Wikipedia URL"),
+            PageError::Missing => write!(f, "page does not exist"),
+            PageError::Network(reason) => write!(f, "network unavailable ({})", reason),
         }
+    }
+}
 
-        Ok(res)
+/// Title of `page` (a title or a url under `wiki_url`) as it appears in links, without asking the wiki.
+/// Same as `normalize_title`, except that urls of other sites and wikis are rejected.
+pub(crate) fn page_title(page: &str, wiki_url: &str) -> Result<String, PageError> {
+    let page = page.trim();
+    let title = if page.starts_with("http://") || page.starts_with("https://") {
+        normalize_title(page.strip_prefix(wiki_url).ok_or(PageError::NotWikiUrl)?)
     }
     else {
-        Ok(vec![])
+        normalize_title(page)
+    };
+    if title.is_empty() {
+        return Err(PageError::Empty);
     }
+    Ok(title)
 }
-fn get_links_bench(from: &str, client: &mut Client, bench: &mut Bench) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let html = get_html_bench(("https://en.wikipedia.org/wiki/".to_string() + &from).as_str(), client, bench)?;
 
-    bench.start(1);
+/// Checks that `page` (a title or a url under `wiki_url`) exists, and returns its title after redirects.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn resolve_page(page: &str, wiki_url: &str, fetch: &FetchOptions) -> Result<String, PageError> {
+    let title = page_title(page, wiki_url)?;
+    let client = fetch.client().map_err(|e| PageError::Network(format!("Invalid network settings: {}", e)))?;
+    let response = client.get(wiki_url.to_string() + title.as_str()).send()
+        .map_err(|e| PageError::Network(e.to_string()))?;
+    match response.status() {
+        StatusCode::NOT_FOUND => Err(PageError::Missing),
+        status if !status.is_success() => Err(PageError::Network(status.to_string())),
+        _ => Ok(response.url().as_str().strip_prefix(wiki_url).map_or(title, str::to_string)),
+    }
+}
 
-    let beg = html.find("<div id=\"mw-content-text\"");
-    if let Some(beg) = beg {
-        let mut res = Vec::new();
+/// Article links of the page `from` on the wiki at `wiki_url`. A missing page has none.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_links(wiki_url: &str, from: &str, client: &mut Client) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    Ok(get_links_bench(wiki_url, from, client, false, &mut Bench::new())?.0)
+}
 
-        let mut x = &html[beg..];
-        
-        while let Some(ind) = x.find("<a href=\"/wiki/") {
-            x = &x[ind + 15..];
-            let ref_end = x.find("\"").unwrap();
-            let r = &x[..ref_end];
-            if !r.contains(':') {
-                res.push(r.to_string());
-            }
-            x = &x[ref_end..];
-        }
+/// With `link_context`, also the context of the first link to every page.
+#[cfg(not(target_arch = "wasm32"))]
+fn get_links_bench(wiki_url: &str, from: &str, client: &mut Client, link_context: bool, bench: &mut Bench)
+        -> Result<(Vec<String>, Vec<LinkContext>), Box<dyn std::error::Error>> {
+    let html = match bench.span("fetch", || get_html((wiki_url.to_string() + from).as_str(), client)) {
+        Ok(html) => html,
+        // A missing page (a red link) is a dead end, not a reason to kill the thread. Other error statuses still are
+        Err(e) if e.downcast_ref::<reqwest::Error>().and_then(|e| e.status()) == Some(StatusCode::NOT_FOUND) => return Ok((vec![], vec![])),
+        Err(e) => return Err(e),
+    };
 
-        bench.stop(1);
-        Ok(res)
-    }
-    else {
-        bench.stop(1);
-        Ok(vec![])
+    let links = bench.span("parse", || scan_links(html.as_str()).into_iter().map(|(_, r)| r.to_string()).collect());
+    let context = if link_context { bench.span("context", || context::page_context(html.as_str(), from)) } else { Vec::new() };
+    Ok((links, context))
+}
+
+/// Article links in the content of a page: the offset of every `<a` tag in `html` and the title it links to.
+pub(crate) fn scan_links(html: &str) -> Vec<(usize, &str)> {
+    let mut res = Vec::new();
+    let mut x = match html.find("<div id=\"mw-content-text\"") {
+        Some(beg) => beg,
+        None => return res,
+    };
+
+    while let Some(ind) = html[x..].find("<a href=\"/wiki/") {
+        let tag = x + ind;
+        let ref_start = tag + 15;
+        let ref_end = ref_start + html[ref_start..].find('"').unwrap();
+        let r = &html[ref_start..ref_end];
+        if !r.contains(':') {
+            res.push((tag, r));
+        }
+        x = ref_end;
     }
+    res
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 fn collect_benches(bench_reciever: &Receiver<Bench>) -> Bench {
     let mut benches = Vec::new();
     loop {
-        let recv_res = bench_reciever.try_recv();
-        if recv_res.is_err() {
-            if recv_res.unwrap_err() == TryRecvError::Empty {
-                break;
-            }
-        }
-        else {
-            benches.push(recv_res.unwrap());
+        match bench_reciever.try_recv() {
+            Ok(bench) => benches.push(bench),
+            Err(TryRecvError::Empty) => break,
+            Err(TryRecvError::Disconnected) => {},
         }
     }
 
@@ -105,23 +150,25 @@ fn collect_benches(bench_reciever: &Receiver<Bench>) -> Bench {
     res
 }
 
-fn write_bench_results(bench_results: &Bench, path: &str) {
-    let file = File::create(path);
+/// In the format guessed from the extension of `path`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn write_bench_results(bench_results: &Bench, path: &str) {
+    if let Err(e) = bench_results.write(Path::new(path)) {
+        eprintln!("Error while writing the bench results file({}): {:?}", path, e);
+    }
+}
 
-    if file.is_err() {
-        eprintln!("Error while creating a bench results file({}): {:?}", path, file.unwrap_err());
+#[cfg(not(target_arch = "wasm32"))]
+fn kill_threads(txs: Vec<Sender<String>>, handlers: Vec<JoinHandle<()>>) {
+    for tx in txs {
+        let _ = tx.send("kill".to_string());
     }
-    else {
-        let mut file = file.unwrap();
-        for i in 0..=255u8 {
-            let dur = bench_results.get_duration(i);
-            if dur.as_nanos() > 0 {
-                file.write_all(format!("{}: {}s\n", i, dur.as_secs_f64()).as_bytes()).unwrap();
-            }
-        }
+    for handler in handlers {
+        let _ = handler.join();
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 #[derive(PartialEq, Eq)]
 enum ThreadState {
     Idle,
@@ -129,186 +176,334 @@ enum ThreadState {
     Error,
 }
 
-#[allow(dead_code)]
-pub fn search(from: &str, to: &str, num_of_threads: usize, max_num_of_links: usize, num_of_links_sender: Sender<(usize, usize, usize)>, dead_threads_sender: Sender<usize>) -> Vec<String> {
-    if from == to {
-        return vec![from.to_string()];
-    }
-    let from = &from[from.rfind('/').unwrap() + 1..];
-    let to = &to[to.rfind('/').unwrap() + 1..];
-
-    let mut all = HashMap::new();
-    all.insert(from.to_string(), "".to_string());
-
-    let mut in_search = LinkedList::new();
-    in_search.push_back(from.to_string());
+/// What a worker thread has done so far, reported with the progress.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkerStatus {
+    /// Page being fetched.
+    pub page: Option<String>,
+    pub pages_done: usize,
+    /// Time spent on all the done pages.
+    pub busy_time: Duration,
+    pub last_fetch: Option<Duration>,
+    /// Fetches that failed. Every failure ends the worker, the search may start it again.
+    pub errors: usize,
+    /// Why the last failed fetch failed.
+    pub last_error: Option<String>,
+    /// Pages it got that had failed before.
+    pub retries: usize,
+    /// Times it was started again after dying.
+    pub restarts: usize,
+    pub alive: bool,
+}
 
-    let mut in_search_next = LinkedList::new();
+impl WorkerStatus {
+    pub fn average_fetch(&self) -> Option<Duration> {
+        if self.pages_done == 0 { None } else { Some(self.busy_time / self.pages_done as u32) }
+    }
+}
 
-    let mut txs = Vec::new();
-    let mut rxs = Vec::new();
-    let mut handlers = Vec::new();
-    let mut states = Vec::new();
-    let mut plinks: Vec<Option<String>> = Vec::new();
+/// Limits after which the search gives up. Zero (or `None`) means no limit.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Limits {
+    /// Max number of links waiting in the queue.
+    pub max_in_queue: usize,
+    /// Max number of pages processed over the whole search.
+    pub max_processed: usize,
+    /// Max number of links in the path (per leg when there are waypoints).
+    pub max_depth: usize,
+    pub max_duration: Option<Duration>,
+}
 
-    for _ in 0..num_of_threads {
-        let (tx1, rx) = mpsc::channel(); // from main thread
-        let (tx, rx1) = mpsc::channel(); // to main thread
-        txs.push(tx1);
-        rxs.push(rx1);
-
-        handlers.push(thread::spawn(move || {
-            let mut client = Client::default();
-            
-            loop {
-                let url = rx.recv();
-                if url.is_err() {
-                    break;
-                }
-                let url: String = url.unwrap();
-                if url == "kill".to_string() {
-                    break;
-                }
-                if tx.send(get_links(url.as_str(), &mut client).unwrap()).is_err() {
-                    break;
-                }
-            }
-        }));
+/// How a search ended.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SearchOutcome {
+    Found(Vec<String>),
+    /// Every reachable page was processed and the target is not among them.
+    NoPath,
+    LimitReached(String),
+    /// The receiving side of the progress channel is closed.
+    Cancelled,
+    Error(String),
+}
 
-        states.push(ThreadState::Idle);
-        plinks.push(None);
+impl SearchOutcome {
+    pub fn path(&self) -> &[String] {
+        match self {
+            SearchOutcome::Found(path) => path,
+            _ => &[],
+        }
     }
 
-    let mut processed = 0usize;
-
-    let mut depth_level = 1usize;
+    /// Short machine readable name of the outcome.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SearchOutcome::Found(_) => "found",
+            SearchOutcome::NoPath => "no_path",
+            SearchOutcome::LimitReached(_) => "limit_reached",
+            SearchOutcome::Cancelled => "cancelled",
+            SearchOutcome::Error(_) => "error",
+        }
+    }
+}
 
-    let mut num_of_links_changed = true;
-    // while path betweeen links is not found
-    loop {
-        // while every link is in_search is not processed
-        while !in_search.is_empty() || states.contains(&ThreadState::Processing) {
-            if num_of_links_changed {
-                let res = num_of_links_sender.send((processed, in_search.len() + in_search_next.len(), depth_level));
-                if res.is_err() {
-                    eprintln!("Main thread is closed");
+impl fmt::Display for SearchOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchOutcome::Found(path) => write!(f, "Path of {} link{} found", path.len() - 1, if path.len() == 2 {""} else {"s"}),
+            SearchOutcome::NoPath => write!(f, "There is no path between the pages"),
+            SearchOutcome::LimitReached(limit) => write!(f, "Search limit reached: {}", limit),
+            SearchOutcome::Cancelled => write!(f, "Search was cancelled"),
+            SearchOutcome::Error(e) => write!(f, "Search failed: {}", e),
+        }
+    }
+}
 
-                    for tx in txs {
-                        let _ = tx.send("kill".to_string());
-                    }
-                    for handler in handlers {
-                        let _ = handler.join();
-                    }
+/// How pages are fetched.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FetchOptions {
+    /// Time a single request may take. `None` waits forever.
+    pub timeout: Option<Duration>,
+    pub user_agent: String,
+    /// Url of the proxy every request goes through. The system proxy is used when empty.
+    pub proxy: String,
+}
 
-                    return vec![];
-                }
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Some(Duration::from_secs(30)),
+            user_agent: USER_AGENT.to_string(),
+            proxy: String::new(),
+        }
+    }
+}
 
-                num_of_links_changed = false;
-            }
+#[cfg(not(target_arch = "wasm32"))]
+impl FetchOptions {
+    /// Fails on a proxy url that can't be parsed.
+    pub(crate) fn client(&self) -> reqwest::Result<Client> {
+        let mut builder = Client::builder().timeout(self.timeout).user_agent(self.user_agent.as_str());
+        if !self.proxy.is_empty() {
+            builder = builder.proxy(reqwest::Proxy::all(self.proxy.as_str())?);
+        }
+        builder.build()
+    }
+}
 
-            if max_num_of_links > 0 && in_search.len() + in_search_next.len() >= max_num_of_links {
-                eprintln!("Max number of links in the queue exceeded");
+/// Everything a search needs besides the two pages.
+#[derive(Clone)]
+pub struct SearchConfig {
+    /// Pages are fetched from `wiki_url` + title.
+    pub wiki_url: String,
+    pub threads: usize,
+    pub constraints: Constraints,
+    pub limits: Limits,
+    /// Links of fetched pages are taken from and stored to the cache.
+    pub cache: LinkCache,
+    /// How much of the explored graph is kept for `SearchReport::graph`.
+    pub graph_recording: GraphRecording,
+    /// How many times a worker that died is started again. Zero means dead workers stay dead.
+    pub worker_restarts: usize,
+    /// Every page that is not in the cache waits for it before being fetched.
+    pub rate_limiter: RateLimiter,
+    pub fetch: FetchOptions,
+    /// Records the links of every page, or replays them instead of fetching.
+    pub archive: ArchiveMode,
+    /// Keeps the context of the link that discovered every page, for `SearchReport::context`.
+    pub link_context: bool,
+}
 
-                for tx in txs {
-                    let _ = tx.send("kill".to_string());
-                }
-                for handler in handlers {
-                    let _ = handler.join();
-                }
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            wiki_url: WIKI_URL.to_string(),
+            threads: 1,
+            constraints: Constraints::default(),
+            limits: Limits::default(),
+            cache: LinkCache::new(0),
+            graph_recording: GraphRecording::Off,
+            worker_restarts: 0,
+            rate_limiter: RateLimiter::unlimited(),
+            fetch: FetchOptions::default(),
+            archive: ArchiveMode::Off,
+            link_context: false,
+        }
+    }
+}
 
-                return vec![];
-            }
+/// What happens at the edge of the BFS, for drawing the search as it goes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrontierEvent {
+    /// A page was seen for the first time. The start of every leg has no parent.
+    Discovered { title: String, parent: Option<String>, depth: usize },
+    Fetching { thread: usize, title: String },
+    /// The thread is done with its page, or died.
+    Fetched { thread: usize },
+}
 
-            for i in 0..num_of_threads {
-                if states[i] == ThreadState::Processing {
-                    let r = rxs[i].try_recv();
+/// State shared by all the legs of one search.
+#[cfg(not(target_arch = "wasm32"))]
+struct SearchContext<'a> {
+    config: &'a SearchConfig,
+    deadline: Option<Instant>,
+    progress_sender: &'a Sender<Progress>,
+    dead_threads_sender: &'a Sender<usize>,
+    frontier_sender: Option<&'a Sender<FrontierEvent>>,
+    processed: usize,
+    bench: Bench,
+    /// Whether the benches keep a timeline of every call.
+    timeline: bool,
+    /// Index of the current leg.
+    leg: usize,
+    graph: ExploredGraph,
+    workers: Vec<WorkerStatus>,
+    /// Context of every link of the path found so far, `None` for the links of pages that came from the cache.
+    context: Vec<Option<LinkContext>>,
+}
 
-                    match r {
-                        Ok(v) => {
-                            processed += 1;
-                            num_of_links_changed = true;
+#[cfg(not(target_arch = "wasm32"))]
+impl SearchContext<'_> {
+    fn send_progress(&self, in_queue: usize, depth: usize) -> bool {
+        self.progress_sender.send(Progress { processed: self.processed, in_queue, depth, workers: self.workers.clone() }).is_ok()
+    }
 
-                            for c in &v {
-                                if c == to {
-                                    let mut res = vec![];
-                    
-                                    let mut li = c.clone();
-                                    res.push(li.clone());
-                                    li = plinks[i].clone().unwrap();
-                                    res.push(li.clone());
-                    
-                                    while li != from {
-                                        li = all.get(&li).unwrap().clone();
-                                        res.push(li.clone());
-                                    }
-                                    res.reverse();
+    fn frontier(&self, event: FrontierEvent) {
+        if let Some(sender) = self.frontier_sender {
+            let _ = sender.send(event);
+        }
+    }
 
-                                    for tx in txs {
-                                        let _ = tx.send("kill".to_string());
-                                    }
-                                    for handler in handlers {
-                                        let _ = handler.join();
-                                    }
-                                    return res;
-                                }
-                                
-                                if !all.contains_key(c) {
-                                    all.insert(c.clone(), plinks[i].clone().unwrap());
-                                    in_search_next.push_back(c.clone());
-                                    num_of_links_changed = true;
-                                }
-                            }
+    fn discovered(&self, title: &str, parent: Option<&str>, depth: usize) {
+        if self.frontier_sender.is_some() {
+            self.frontier(FrontierEvent::Discovered { title: title.to_string(), parent: parent.map(str::to_string), depth });
+        }
+    }
+}
 
-                            states[i] = ThreadState::Idle;
-                            plinks[i] = None;
-                        },
-                        Err(e) => {
-                            if e == TryRecvError::Disconnected {
-                                states[i] = ThreadState::Error;
-                                in_search.push_front(plinks[i].clone().unwrap());
-                                num_of_links_changed = true;
-                                plinks[i] = None;
-
-                                eprintln!("Thread {} died", i);
-                                let _ = dead_threads_sender.send(i);
+/// Links of a page, the context of the ones it has (see `SearchConfig::link_context`) and the time it took to get them.
+#[cfg(not(target_arch = "wasm32"))]
+type PageLinks = (Vec<String>, Vec<LinkContext>, Duration);
+
+/// Sender of titles to a worker, receiver of their links (or of why they could not be fetched), and the worker thread.
+#[cfg(not(target_arch = "wasm32"))]
+type WorkerChannels = (Sender<String>, Receiver<Result<PageLinks, String>>, JoinHandle<()>);
+
+/// Starts a thread that fetches the links of every title it gets, with the time it took, until it gets "kill".
+/// After a failed fetch it sends the error and stops.
+/// Its bench is on timeline row `worker + 1`, the row of the search thread is 0.
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_worker(config: &SearchConfig, worker: usize, timeline: bool, bench_sender: &Sender<Bench>) -> WorkerChannels {
+    let (tx1, rx) = mpsc::channel(); // from main thread
+    let (tx, rx1) = mpsc::channel(); // to main thread
+
+    let bench_sender = bench_sender.clone();
+    let cache = config.cache.clone();
+    let wiki_url = config.wiki_url.clone();
+    let rate_limiter = config.rate_limiter.clone();
+    let fetch = config.fetch.clone();
+    let archive = config.archive.clone();
+    let link_context = config.link_context;
+
+    let handler = thread::spawn(move || {
+        // The options were checked before the search started
+        let mut client = fetch.client().unwrap_or_default();
+
+        let mut bench = if timeline { Bench::with_timeline(worker + 1, format!("worker {}", worker)) } else { Bench::new() };
+        loop {
+            let url = rx.recv();
+            if url.is_err() {
+                break;
+            }
+            let url: String = url.unwrap();
+            if url == "kill" {
+                break;
+            }
+            let start = Instant::now();
+            bench.start("page");
+            let (links, context) = match &archive {
+                ArchiveMode::Replay(archive) => bench.span("replay", || {
+                    let context = if link_context { archive.context(url.as_str()) } else { Vec::new() };
+                    (archive.get(url.as_str()).unwrap_or_default(), context)
+                }),
+                _ => {
+                    // The cache keeps no html, so no context either
+                    let (links, context) = match bench.span("cache", || cache.get(url.as_str())) {
+                        Some(links) => (links, Vec::new()),
+                        None => {
+                            bench.span("rate limit", || rate_limiter.wait());
+                            match get_links_bench(wiki_url.as_str(), url.as_str(), &mut client, link_context, &mut bench) {
+                                Ok((links, context)) => {
+                                    cache.insert(url.as_str(), &links);
+                                    (links, context)
+                                },
+                                Err(e) => {
+                                    bench.stop("page");
+                                    let _ = tx.send(Err(e.to_string()));
+                                    break;
+                                },
                             }
                         },
-                    }
-                }
-            }
-
-            for i in 0..num_of_threads {
-                if states[i] == ThreadState::Idle {
-                    if !in_search.is_empty() {
-                        let link = in_search.pop_front().unwrap();
-                        num_of_links_changed = true;
-                        if txs[i].send(link.clone()).is_err() {
-                            states[i] = ThreadState::Error;
-                            eprintln!("Error while sending to thread №{}", i);
+                    };
+                    if let ArchiveMode::Record(archive) = &archive {
+                        archive.insert(url.as_str(), &links);
+                        if !context.is_empty() {
+                            archive.insert_context(url.as_str(), &context);
                         }
-                        states[i] = ThreadState::Processing;
-                        plinks[i] = Some(link.clone());
-
-                        //println!("List size is {}. Checking {}", in_search.len() + in_search_next.len(), link);
                     }
-                }
+                    (links, context)
+                },
+            };
+            bench.stop("page");
+            if tx.send(Ok((links, context, start.elapsed()))).is_err() {
+                break;
             }
         }
+        bench_sender.send(bench).unwrap();
+    });
+    (tx1, rx1, handler)
+}
 
-        swap(&mut in_search, &mut in_search_next);
-        depth_level += 1;
+/// Reports that worker `i` died while it had `page`, and starts a new one in its place if it may still be restarted.
+#[cfg(not(target_arch = "wasm32"))]
+fn worker_died(ctx: &mut SearchContext<'_>, i: usize, page: &str, error: String, bench_sender: &Sender<Bench>) -> Option<WorkerChannels> {
+    eprintln!("Thread {} died on {}: {}", i, page, error);
+    let _ = ctx.dead_threads_sender.send(i);
+
+    let worker = &mut ctx.workers[i];
+    worker.page = None;
+    worker.errors += 1;
+    worker.last_error = Some(error);
+    if worker.restarts < ctx.config.worker_restarts {
+        worker.restarts += 1;
+        Some(spawn_worker(ctx.config, i, ctx.timeline, bench_sender))
+    }
+    else {
+        worker.alive = false;
+        None
     }
 }
 
-pub fn search_bench(from: &str, to: &str, num_of_threads: usize, max_num_of_links: usize, num_of_links_sender: Sender<(usize, usize, usize)>, dead_threads_sender: Sender<usize>) -> Vec<String> {
+/// Plain BFS from `from` to `to` (both are page titles). Pages for which `is_excluded` returns true are never expanded.
+#[cfg(not(target_arch = "wasm32"))]
+fn search_leg<F>(from: &str, to: &str, is_excluded: F, ctx: &mut SearchContext<'_>) -> SearchOutcome
+    where F: Fn(&str) -> bool {
     if from == to {
-        return vec![from.to_string()];
+        return SearchOutcome::Found(vec![from.to_string()]);
+    }
+
+    let num_of_threads = ctx.config.threads;
+    let limits = &ctx.config.limits;
+    let recording = ctx.config.graph_recording;
+    if recording != GraphRecording::Off {
+        ctx.graph.add_node(from, ctx.leg, 0, None);
     }
-    let from = &from[from.rfind('/').unwrap() + 1..];
-    let to = &to[to.rfind('/').unwrap() + 1..];
+    ctx.discovered(from, None, 0);
 
     let mut all = HashMap::new();
     all.insert(from.to_string(), "".to_string());
+    // Context of the link from the parent of every page, when it is looked up
+    let mut contexts: HashMap<String, LinkContext> = HashMap::new();
 
     let mut in_search = LinkedList::new();
     in_search.push_back(from.to_string());
@@ -323,158 +518,315 @@ pub fn search_bench(from: &str, to: &str, num_of_threads: usize, max_num_of_link
 
     let (bench_sender, bench_reciever) = mpsc::channel();
 
-    for _ in 0..num_of_threads {
-        let (tx1, rx) = mpsc::channel(); // from main thread
-        let (tx, rx1) = mpsc::channel(); // to main thread
-        txs.push(tx1);
-        rxs.push(rx1);
-
-        let bench_sender = bench_sender.clone();
-
-        handlers.push(thread::spawn(move || {
-            let mut client = Client::default();
-            
-            let mut bench = Bench::new();
-            loop {
-                let url = rx.recv();
-                if url.is_err() {
-                    break;
-                }
-                let url: String = url.unwrap();
-                if url == "kill".to_string() {
-                    break;
-                }
-                if tx.send(get_links_bench(url.as_str(), &mut client, &mut bench).unwrap()).is_err() {
-                    break;
-                }
-            }
-            bench_sender.send(bench).unwrap();
-        }));
+    for i in 0..num_of_threads {
+        let (tx, rx, handler) = spawn_worker(ctx.config, i, ctx.timeline, &bench_sender);
+        txs.push(tx);
+        rxs.push(rx);
+        handlers.push(handler);
 
         states.push(ThreadState::Idle);
         plinks.push(None);
+        ctx.workers[i].alive = true;
     }
-
-    let mut processed = 0usize;
+    // Pages whose fetch killed a worker
+    let mut failed = HashSet::new();
 
     let mut depth_level = 0usize;
 
     let mut num_of_links_changed = true;
+    // Everything the main thread does while the workers fetch
+    ctx.bench.start("coordination");
     // while path betweeen links is not found
-    loop {
+    let outcome = 'search: loop {
         // while every link is in_search is not processed
         while !in_search.is_empty() || states.contains(&ThreadState::Processing) {
             if num_of_links_changed {
-                let res = num_of_links_sender.send((processed, in_search.len() + in_search_next.len(), depth_level));
-                if res.is_err() {
+                if !ctx.send_progress(in_search.len() + in_search_next.len(), depth_level) {
                     eprintln!("Main thread is closed");
-
-                    for tx in txs {
-                        let _ = tx.send("kill".to_string());
-                    }
-                    for handler in handlers {
-                        let _ = handler.join();
-                    }
-                    write_bench_results(&collect_benches(&bench_reciever), "bench.txt");
-
-                    return vec![];
+                    break 'search SearchOutcome::Cancelled;
                 }
 
                 num_of_links_changed = false;
             }
 
-            if max_num_of_links > 0 && in_search.len() + in_search_next.len() >= max_num_of_links {
+            if limits.max_in_queue > 0 && in_search.len() + in_search_next.len() >= limits.max_in_queue {
                 eprintln!("Max number of links in the queue exceeded");
+                break 'search SearchOutcome::LimitReached(format!("more than {} links in the queue", limits.max_in_queue));
+            }
+            if limits.max_processed > 0 && ctx.processed >= limits.max_processed {
+                eprintln!("Max number of processed pages exceeded");
+                break 'search SearchOutcome::LimitReached(format!("{} pages processed", limits.max_processed));
+            }
+            if ctx.deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                eprintln!("Search time is out");
+                break 'search SearchOutcome::LimitReached(format!("search took longer than {}s", limits.max_duration.unwrap().as_secs_f32()));
+            }
 
-                for tx in txs {
-                    let _ = tx.send("kill".to_string());
-                }
-                for handler in handlers {
-                    let _ = handler.join();
-                }
-                write_bench_results(&collect_benches(&bench_reciever), "bench.txt");
-
-                return vec![];
+            if !states.contains(&ThreadState::Idle) && !states.contains(&ThreadState::Processing) {
+                eprintln!("All threads are dead");
+                break 'search SearchOutcome::Error("all threads are dead".to_string());
             }
 
             for i in 0..num_of_threads {
                 if states[i] == ThreadState::Processing {
                     let r = rxs[i].try_recv();
 
-                    match r {
-                        Ok(v) => {
-                            processed += 1;
+                    let error = match r {
+                        Ok(Ok((v, page_context, fetch_time))) => {
+                            ctx.frontier(FrontierEvent::Fetched { thread: i });
+                            ctx.processed += 1;
                             num_of_links_changed = true;
-
+                            let worker = &mut ctx.workers[i];
+                            worker.page = None;
+                            worker.pages_done += 1;
+                            worker.busy_time += fetch_time;
+                            worker.last_fetch = Some(fetch_time);
+
+                            let parent = plinks[i].clone().unwrap();
+                            ctx.bench.start("dedup");
+                            let mut page_context: HashMap<String, LinkContext> = page_context.into_iter().map(|c| (c.to.clone(), c)).collect();
                             for c in &v {
+                                if recording == GraphRecording::AllLinks {
+                                    ctx.graph.add_link(parent.as_str(), c);
+                                }
+
                                 if c == to {
+                                    if recording != GraphRecording::Off {
+                                        ctx.graph.add_node(c, ctx.leg, depth_level + 1, Some(parent.as_str()));
+                                    }
+                                    ctx.discovered(c, Some(parent.as_str()), depth_level + 1);
+                                    if let Some(context) = page_context.remove(c) {
+                                        contexts.insert(c.clone(), context);
+                                    }
+
                                     let mut res = vec![];
-                    
+
                                     let mut li = c.clone();
                                     res.push(li.clone());
                                     li = plinks[i].clone().unwrap();
                                     res.push(li.clone());
-                    
+
                                     while li != from {
                                         li = all.get(&li).unwrap().clone();
                                         res.push(li.clone());
                                     }
                                     res.reverse();
+                                    if ctx.config.link_context {
+                                        ctx.context.extend(res[1..].iter().map(|page| contexts.remove(page)));
+                                    }
+
+                                    ctx.bench.stop("dedup");
+                                    break 'search SearchOutcome::Found(res);
+                                }
 
-                                    for tx in txs {
-                                        let _ = tx.send("kill".to_string());
+                                if !all.contains_key(c) && !is_excluded(c) {
+                                    if recording != GraphRecording::Off {
+                                        ctx.graph.add_node(c, ctx.leg, depth_level + 1, Some(parent.as_str()));
                                     }
-                                    for handler in handlers {
-                                        let _ = handler.join();
+                                    ctx.discovered(c, Some(parent.as_str()), depth_level + 1);
+                                    all.insert(c.clone(), parent.clone());
+                                    if let Some(context) = page_context.remove(c) {
+                                        contexts.insert(c.clone(), context);
                                     }
-                                    write_bench_results(&collect_benches(&bench_reciever), "bench.txt");
-                                    return res;
-                                }
-                                
-                                if !all.contains_key(c) {
-                                    all.insert(c.clone(), plinks[i].clone().unwrap());
                                     in_search_next.push_back(c.clone());
                                     num_of_links_changed = true;
                                 }
                             }
+                            ctx.bench.stop("dedup");
 
                             states[i] = ThreadState::Idle;
                             plinks[i] = None;
+                            continue;
                         },
-                        Err(e) => {
-                            if e == TryRecvError::Disconnected {
-                                states[i] = ThreadState::Error;
-                                in_search.push_front(plinks[i].clone().unwrap());
-                                num_of_links_changed = true;
-                                plinks[i] = None;
-
-                                eprintln!("Thread {} died", i);
-                                let _ = dead_threads_sender.send(i);
-                            }
-                        },
+                        Ok(Err(e)) => e,
+                        Err(TryRecvError::Disconnected) => "the thread panicked".to_string(),
+                        Err(TryRecvError::Empty) => continue,
+                    };
+
+                    states[i] = ThreadState::Error;
+                    let link = plinks[i].take().unwrap();
+                    failed.insert(link.clone());
+                    in_search.push_front(link.clone());
+                    num_of_links_changed = true;
+                    ctx.frontier(FrontierEvent::Fetched { thread: i });
+
+                    if let Some((tx, rx, handler)) = worker_died(ctx, i, link.as_str(), error, &bench_sender) {
+                        txs[i] = tx;
+                        rxs[i] = rx;
+                        // The old thread is over, it stopped after sending the error
+                        let _ = std::mem::replace(&mut handlers[i], handler).join();
+                        states[i] = ThreadState::Idle;
                     }
                 }
             }
 
             for i in 0..num_of_threads {
-                if states[i] == ThreadState::Idle {
-                    if !in_search.is_empty() {
-                        let link = in_search.pop_front().unwrap();
-                        num_of_links_changed = true;
-                        if txs[i].send(link.clone()).is_err() {
-                            states[i] = ThreadState::Error;
-                            eprintln!("Error while sending to thread №{}", i);
+                if states[i] == ThreadState::Idle && !in_search.is_empty() {
+                    let link = in_search.pop_front().unwrap();
+                    num_of_links_changed = true;
+                    if ctx.bench.span("queueing", || txs[i].send(link.clone())).is_err() {
+                        states[i] = ThreadState::Error;
+                        in_search.push_front(link.clone());
+                        if let Some((tx, rx, handler)) = worker_died(ctx, i, link.as_str(), "the thread stopped taking pages".to_string(), &bench_sender) {
+                            txs[i] = tx;
+                            rxs[i] = rx;
+                            let _ = std::mem::replace(&mut handlers[i], handler).join();
+                            states[i] = ThreadState::Idle;
                         }
-                        states[i] = ThreadState::Processing;
-                        plinks[i] = Some(link.clone());
-
-                        //println!("List size is {}. Checking {}", in_search.len() + in_search_next.len(), link);
+                        continue;
+                    }
+                    states[i] = ThreadState::Processing;
+                    ctx.frontier(FrontierEvent::Fetching { thread: i, title: link.clone() });
+                    if failed.contains(&link) {
+                        ctx.workers[i].retries += 1;
                     }
+                    ctx.workers[i].page = Some(link.clone());
+                    plinks[i] = Some(link.clone());
+
+                    //println!("List size is {}. Checking {}", in_search.len() + in_search_next.len(), link);
                 }
             }
         }
 
+        if in_search_next.is_empty() {
+            eprintln!("There is no path between the pages");
+            break 'search SearchOutcome::NoPath;
+        }
+
         swap(&mut in_search, &mut in_search_next);
         depth_level += 1;
+
+        if limits.max_depth > 0 && depth_level >= limits.max_depth {
+            eprintln!("Max search depth exceeded");
+            break 'search SearchOutcome::LimitReached(format!("no path of {} links or less", limits.max_depth));
+        }
+    };
+
+    ctx.bench.stop("coordination");
+
+    // Numbers after the last processed page: the loop sends them before it looks at new pages, so the page that ended
+    // the search would be missing from the last progress
+    for worker in &mut ctx.workers {
+        worker.page = None;
+    }
+    ctx.send_progress(in_search.len() + in_search_next.len(), depth_level);
+
+    kill_threads(txs, handlers);
+    ctx.bench.combine(&collect_benches(&bench_reciever));
+    outcome
+}
+
+/// Runs one leg per pair of consecutive pages in `from -> waypoints -> to` and joins the results.
+/// Pages that are already on the path are excluded from the next legs, and the stops that come later from the current one,
+/// so the joined path never repeats itself and visits the stops in order.
+#[cfg(not(target_arch = "wasm32"))]
+fn search_constrained(from: &str, to: &str, ctx: &mut SearchContext<'_>) -> SearchOutcome {
+    let constraints = &ctx.config.constraints;
+    let mut stops = vec![normalize_title(from)];
+    stops.extend(constraints.waypoints.iter().map(|w| normalize_title(w)));
+    stops.push(normalize_title(to));
+
+    let mut path: Vec<String> = vec![];
+    for (i, leg) in stops.windows(2).enumerate() {
+        ctx.leg = i;
+        let mut on_path: HashSet<&str> = path.iter().map(String::as_str).collect();
+        on_path.extend(stops[i + 2..].iter().map(String::as_str).filter(|s| *s != leg[1]));
+        let leg_path = match search_leg(leg[0].as_str(), leg[1].as_str(),
+                |title| constraints.is_excluded(title) || on_path.contains(title), ctx) {
+            SearchOutcome::Found(leg_path) => leg_path,
+            outcome => return outcome,
+        };
+
+        if path.is_empty() {
+            path = leg_path;
+        }
+        else {
+            path.extend(leg_path.into_iter().skip(1));
+        }
+    }
+    SearchOutcome::Found(path)
+}
+
+/// Everything a search produces besides the progress.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct SearchRun {
+    pub outcome: SearchOutcome,
+    pub bench: Bench,
+    pub graph: ExploredGraph,
+    /// Context of every link of the found path when it was asked for, `None` for the ones that were not looked up.
+    pub context: Vec<Option<LinkContext>>,
+}
+
+/// With `timeline`, the bench keeps every call of every thread (see `Bench::with_timeline`).
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn run_search(from: &str, to: &str, config: &SearchConfig, progress_sender: &Sender<Progress>,
+        dead_threads_sender: &Sender<usize>, frontier_sender: Option<&Sender<FrontierEvent>>, timeline: bool) -> SearchRun {
+    if let Err(e) = config.fetch.client() {
+        return SearchRun {
+            outcome: SearchOutcome::Error(format!("Invalid network settings: {}", e)),
+            bench: Bench::new(),
+            graph: ExploredGraph::default(),
+            context: Vec::new(),
+        };
+    }
+    let mut ctx = SearchContext {
+        config,
+        deadline: config.limits.max_duration.map(|d| Instant::now() + d),
+        progress_sender,
+        dead_threads_sender,
+        frontier_sender,
+        processed: 0,
+        bench: if timeline { Bench::with_timeline(0, "search") } else { Bench::new() },
+        timeline,
+        leg: 0,
+        graph: ExploredGraph::default(),
+        workers: vec![WorkerStatus::default(); config.threads],
+        context: Vec::new(),
+    };
+    let outcome = search_constrained(from, to, &mut ctx);
+    ctx.graph.path = outcome.path().to_vec();
+    SearchRun {
+        context: if outcome.path().is_empty() { Vec::new() } else { ctx.context },
+        outcome,
+        bench: ctx.bench,
+        graph: ctx.graph,
     }
-}
\ No newline at end of file
+}
+
+/// Searches for the shortest path from `from` to `to` (page titles or urls) that satisfies `config.constraints`.
+/// Sends (num_of_processed, num_in_queue, search_depth) whenever the numbers change and the index of every thread that died.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn search(from: &str, to: &str, config: &SearchConfig, num_of_links_sender: Sender<(usize, usize, usize)>, dead_threads_sender: Sender<usize>) -> SearchOutcome {
+    let (progress_sender, forwarder) = forward_progress(num_of_links_sender);
+    let outcome = run_search(from, to, config, &progress_sender, &dead_threads_sender, None, false).outcome;
+    drop(progress_sender);
+    let _ = forwarder.join();
+    outcome
+}
+
+/// Passes the numbers of every progress to `num_of_links_sender`, until either side is closed.
+#[cfg(not(target_arch = "wasm32"))]
+fn forward_progress(num_of_links_sender: Sender<(usize, usize, usize)>) -> (Sender<Progress>, JoinHandle<()>) {
+    let (progress_sender, progress_reciever) = mpsc::channel::<Progress>();
+    let forwarder = thread::spawn(move || {
+        for p in progress_reciever {
+            if num_of_links_sender.send((p.processed, p.in_queue, p.depth)).is_err() {
+                break;
+            }
+        }
+    });
+    (progress_sender, forwarder)
+}
+
+/// Same as `search`, but also writes the time spent in every span of the search to `bench_file`, in the format
+/// its extension asks for (see `BenchFormat`): `.folded` for flamegraph tools, `.trace` for chrome://tracing.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn search_bench(from: &str, to: &str, config: &SearchConfig, num_of_links_sender: Sender<(usize, usize, usize)>, dead_threads_sender: Sender<usize>, bench_file: &str) -> SearchOutcome {
+    let (progress_sender, forwarder) = forward_progress(num_of_links_sender);
+    let timeline = BenchFormat::from_path(Path::new(bench_file)) == BenchFormat::Trace;
+    let run = run_search(from, to, config, &progress_sender, &dead_threads_sender, None, timeline);
+    drop(progress_sender);
+    let _ = forwarder.join();
+    write_bench_results(&run.bench, bench_file);
+    run.outcome
+}
@@ -0,0 +1,310 @@
+//! Builder style entry point of the engine.
+//!
+//! ```no_run
+//! use wikisearch_gui::{Limits, SearchOutcome, Searcher};
+//!
+//! let report = Searcher::builder()
+//!     .threads(8)
+//!     .limits(Limits { max_depth: 6, ..Default::default() })
+//!     .observer(|p: &wikisearch_gui::Progress| eprintln!("{} pages processed", p.processed))
+//!     .run("Rust_(programming_language)", "Philosophy");
+//!
+//! if let SearchOutcome::Found(path) = &report.outcome {
+//!     println!("{}", path.join(" -> "));
+//! }
+//! ```
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::archive::ArchiveMode;
+use crate::bench::{Bench, BenchFormat};
+use crate::cache::LinkCache;
+use crate::constraints::Constraints;
+use crate::context::{self, LinkContext};
+use crate::graph::{ExploredGraph, GraphRecording};
+use crate::rate_limit::RateLimiter;
+use crate::search::{self, FetchOptions, FrontierEvent, Limits, SearchConfig, SearchOutcome, WorkerStatus};
+
+/// The cancel flag is checked at least this often.
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Numbers reported while a search is running.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Pages processed over the whole search.
+    pub processed: usize,
+    /// Links waiting to be processed, in the current level and the one found for the next level.
+    pub in_queue: usize,
+    /// Current BFS level.
+    pub depth: usize,
+    /// One entry per worker thread.
+    pub workers: Vec<WorkerStatus>,
+}
+
+/// Gets told what a running search is doing. Called on the thread that runs the search.
+///
+/// Any `FnMut(&Progress)` closure is an observer that ignores thread deaths.
+pub trait Observer {
+    /// Called whenever the numbers change.
+    fn progress(&mut self, _progress: &Progress) {}
+    /// Called with the index of a worker thread that died. The search goes on with the rest.
+    fn thread_died(&mut self, _thread: usize) {}
+    /// Called for every change of the BFS frontier, if the searcher watches it.
+    fn frontier(&mut self, _event: &FrontierEvent) {}
+}
+
+impl<F: FnMut(&Progress)> Observer for F {
+    fn progress(&mut self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+/// What a finished search returns.
+#[derive(Clone, Debug)]
+pub struct SearchReport {
+    pub outcome: SearchOutcome,
+    /// Last progress reported by the search.
+    pub progress: Progress,
+    /// Indices of the worker threads that died.
+    pub dead_threads: Vec<usize>,
+    pub duration: Duration,
+    /// Explored part of the link graph, empty unless it was recorded.
+    pub graph: ExploredGraph,
+    /// Why every page of the path links to the next one, empty unless it was asked for.
+    pub context: Vec<LinkContext>,
+    /// Why the context is empty although it was asked for and a path was found.
+    pub context_error: Option<String>,
+    /// Time spent in every span of the search, by the main thread and the workers.
+    pub bench: Bench,
+}
+
+/// Runs searches with one configuration. See the module docs for an example.
+pub struct Searcher<'a> {
+    config: SearchConfig,
+    observer: Option<Box<dyn Observer + 'a>>,
+    cancel_flag: Option<Arc<AtomicBool>>,
+    bench_file: Option<String>,
+    watch_frontier: bool,
+}
+
+impl<'a> Searcher<'a> {
+    pub fn builder() -> SearcherBuilder<'a> {
+        SearcherBuilder {
+            searcher: Searcher {
+                config: SearchConfig::default(),
+                observer: None,
+                cancel_flag: None,
+                bench_file: None,
+                watch_frontier: false,
+            },
+        }
+    }
+
+    pub fn config(&self) -> &SearchConfig {
+        &self.config
+    }
+
+    /// Searches for the shortest path from `from` to `to` (page titles or urls), blocking until the search is over.
+    /// The search itself runs on its own threads, the observer is called on this one.
+    pub fn run(&mut self, from: &str, to: &str) -> SearchReport {
+        let (progress_sender, progress_reciever) = mpsc::channel();
+        let (dt_sender, dt_reciever) = mpsc::channel(); // dead_threads
+        let (frontier_sender, frontier_reciever) = mpsc::channel();
+
+        let start_instant = Instant::now();
+        let search_thread = {
+            let (from, to) = (from.to_string(), to.to_string());
+            let config = self.config.clone();
+            let bench_file = self.bench_file.clone();
+            let frontier_sender = if self.watch_frontier { Some(frontier_sender) } else { None };
+            let timeline = bench_file.as_ref().map_or(false, |f| BenchFormat::from_path(Path::new(f)) == BenchFormat::Trace);
+            thread::spawn(move || {
+                let run = search::run_search(from.as_str(), to.as_str(), &config, &progress_sender, &dt_sender, frontier_sender.as_ref(), timeline);
+                if let Some(bench_file) = bench_file {
+                    search::write_bench_results(&run.bench, bench_file.as_str());
+                }
+                let context = match &run.outcome {
+                    SearchOutcome::Found(path) if config.link_context => context::path_context(path, run.context, &config),
+                    _ => Ok(Vec::new()),
+                };
+                (run.outcome, run.graph, context, run.bench)
+            })
+        };
+
+        let mut progress = Progress::default();
+        let mut dead_threads = Vec::new();
+        loop {
+            if self.cancel_flag.as_ref().map_or(false, |c| c.load(Ordering::SeqCst)) {
+                break;
+            }
+
+            match progress_reciever.recv_timeout(CANCEL_CHECK_INTERVAL) {
+                Ok(new_progress) => {
+                    self.pass_frontier(frontier_reciever.try_iter());
+                    progress = new_progress;
+                    if let Some(observer) = &mut self.observer {
+                        observer.progress(&progress);
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => {},
+                // The search is over
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            self.collect_dead_threads(dt_reciever.try_iter(), &mut dead_threads);
+        }
+        // The search notices that nobody listens anymore and stops
+        drop(progress_reciever);
+
+        let (outcome, graph, context, bench) = search_thread.join()
+            .unwrap_or_else(|_| (SearchOutcome::Error("search thread panicked".to_string()), ExploredGraph::default(), Ok(Vec::new()), Bench::new()));
+        let (context, context_error) = match context {
+            Ok(context) => (context, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        self.collect_dead_threads(dt_reciever.try_iter(), &mut dead_threads);
+        self.pass_frontier(frontier_reciever.try_iter());
+
+        SearchReport {
+            outcome,
+            progress,
+            dead_threads,
+            duration: start_instant.elapsed(),
+            graph,
+            context,
+            context_error,
+            bench,
+        }
+    }
+
+    fn pass_frontier(&mut self, events: impl Iterator<Item = FrontierEvent>) {
+        if let Some(observer) = &mut self.observer {
+            for event in events {
+                observer.frontier(&event);
+            }
+        }
+    }
+
+    fn collect_dead_threads(&mut self, new: impl Iterator<Item = usize>, dead_threads: &mut Vec<usize>) {
+        for thread in new {
+            dead_threads.push(thread);
+            if let Some(observer) = &mut self.observer {
+                observer.thread_died(thread);
+            }
+        }
+    }
+}
+
+pub struct SearcherBuilder<'a> {
+    searcher: Searcher<'a>,
+}
+
+impl<'a> SearcherBuilder<'a> {
+    /// Replaces the whole configuration. Later calls change parts of it.
+    pub fn config(mut self, config: SearchConfig) -> Self {
+        self.searcher.config = config;
+        self
+    }
+
+    /// Pages are fetched from `wiki_url` + title. English Wikipedia by default.
+    pub fn source(mut self, wiki_url: impl Into<String>) -> Self {
+        self.searcher.config.wiki_url = wiki_url.into();
+        self
+    }
+
+    /// Number of worker threads fetching pages, 1 by default.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.searcher.config.threads = threads.max(1);
+        self
+    }
+
+    pub fn constraints(mut self, constraints: Constraints) -> Self {
+        self.searcher.config.constraints = constraints;
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.searcher.config.limits = limits;
+        self
+    }
+
+    /// Cache to share with other searchers. Nothing is cached by default.
+    pub fn cache(mut self, cache: LinkCache) -> Self {
+        self.searcher.config.cache = cache;
+        self
+    }
+
+    /// Starts a worker that died again, at most `restarts` times per worker. Dead workers stay dead by default.
+    pub fn worker_restarts(mut self, restarts: usize) -> Self {
+        self.searcher.config.worker_restarts = restarts;
+        self
+    }
+
+    /// Timeout, User-Agent and proxy of the requests.
+    pub fn fetch(mut self, fetch: FetchOptions) -> Self {
+        self.searcher.config.fetch = fetch;
+        self
+    }
+
+    /// Records the links of every page to an archive, or searches only the pages of one. Off by default.
+    pub fn archive(mut self, archive: ArchiveMode) -> Self {
+        self.searcher.config.archive = archive;
+        self
+    }
+
+    /// Limiter to share with other searchers. Requests are not limited by default.
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.searcher.config.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Keeps the explored graph for `SearchReport::graph`. Nothing is kept by default.
+    pub fn graph_recording(mut self, recording: GraphRecording) -> Self {
+        self.searcher.config.graph_recording = recording;
+        self
+    }
+
+    pub fn observer(mut self, observer: impl Observer + 'a) -> Self {
+        self.searcher.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// The search stops with `SearchOutcome::Cancelled` soon after the flag is set.
+    pub fn cancel_flag(mut self, cancel_flag: Arc<AtomicBool>) -> Self {
+        self.searcher.cancel_flag = Some(cancel_flag);
+        self
+    }
+
+    /// Passes every change of the BFS frontier to the observer. Off by default, as there is one event per discovered page.
+    pub fn watch_frontier(mut self, watch: bool) -> Self {
+        self.searcher.watch_frontier = watch;
+        self
+    }
+
+    /// Looks up the anchor text, section and sentence of every link of a found path, for `SearchReport::context`.
+    /// Off by default, as it keeps a sentence per discovered page and the links of pages from the cache are fetched again.
+    pub fn link_context(mut self, link_context: bool) -> Self {
+        self.searcher.config.link_context = link_context;
+        self
+    }
+
+    /// Writes the time spent in every span of the search to `path` after every search, in the format its extension
+    /// asks for (see `BenchFormat`). A `.trace` file has a timeline of every call, for chrome://tracing.
+    pub fn bench_file(mut self, path: impl Into<String>) -> Self {
+        self.searcher.bench_file = Some(path.into());
+        self
+    }
+
+    pub fn build(self) -> Searcher<'a> {
+        self.searcher
+    }
+
+    /// Shortcut for `build().run(from, to)`.
+    pub fn run(self, from: &str, to: &str) -> SearchReport {
+        self.build().run(from, to)
+    }
+}
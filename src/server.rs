@@ -0,0 +1,402 @@
+//! REST API for running searches:
+//!
+//! - `POST /searches` starts a search, the body is a `NewSearch` as JSON
+//! - `GET /searches` lists all searches
+//! - `GET /searches/{id}` returns the status of a search
+//! - `GET /searches/{id}/events` streams the progress of a search as server-sent events
+//! - `DELETE /searches/{id}` cancels a search
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::constraints::{normalize_title, Constraints, TitlePattern};
+use crate::http::{self, Request, RequestError};
+use crate::search::SearchConfig;
+use crate::searcher::{Observer, Progress, Searcher};
+
+/// Longest body of a request. A search request is a few hundred bytes.
+const MAX_BODY_LEN: usize = 16 * 1024;
+
+/// Time a client may take to send its request, or to take a part of the response.
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Progress events are sent to subscribers at most this often.
+const EVENT_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct ServerConfig {
+    /// Used by every search. Its link cache is shared between searches.
+    pub search: SearchConfig,
+    /// Max number of searches running at the same time.
+    pub max_jobs: usize,
+    /// Max number of threads a single search may ask for.
+    pub max_threads: usize,
+    /// Max number of finished searches whose status is kept. The oldest ones are forgotten first.
+    pub max_finished_jobs: usize,
+    /// Max number of connections handled at the same time, event streams included. Others get a 503.
+    pub max_connections: usize,
+}
+
+/// Body of `POST /searches`. Missing options are taken from the server config.
+#[derive(Deserialize)]
+pub struct NewSearch {
+    pub from: String,
+    pub to: String,
+    pub threads: Option<usize>,
+    #[serde(default)]
+    pub via: Vec<String>,
+    #[serde(default)]
+    pub avoid: Vec<String>,
+    pub max_pages: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub timeout_secs: Option<f64>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub id: u64,
+    pub from: String,
+    pub to: String,
+    /// "running" or the kind of the search outcome.
+    pub state: String,
+    pub message: Option<String>,
+    pub path: Vec<String>,
+    pub pages_processed: usize,
+    pub in_queue: usize,
+    pub depth: usize,
+    pub dead_threads: Vec<usize>,
+    pub duration_secs: f64,
+}
+
+#[derive(Serialize)]
+struct ProgressEvent {
+    pages_processed: usize,
+    in_queue: usize,
+    depth: usize,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+}
+
+struct Job {
+    status: JobStatus,
+    cancel: Arc<AtomicBool>,
+    /// Streams subscribed to the events of the job. Dropped when the job is over.
+    subscribers: Vec<Sender<String>>,
+}
+
+impl Job {
+    fn is_running(&self) -> bool {
+        self.status.state == "running"
+    }
+
+    fn broadcast(&mut self, event: &str, data: &impl Serialize) {
+        let message = sse_message(event, data);
+        self.subscribers.retain(|s| s.send(message.clone()).is_ok());
+    }
+}
+
+fn sse_message(event: &str, data: &impl Serialize) -> String {
+    format!("event: {}\ndata: {}\n\n", event, serde_json::to_string(data).unwrap())
+}
+
+struct ServerState {
+    config: ServerConfig,
+    jobs: Mutex<HashMap<u64, Job>>,
+    next_id: AtomicU64,
+    /// Connections being handled.
+    connections: AtomicUsize,
+}
+
+/// Takes a connection slot, gives it back when dropped. `None` if all the slots are taken.
+struct ConnectionSlot(Arc<ServerState>);
+
+impl ConnectionSlot {
+    fn take(state: &Arc<ServerState>) -> Option<Self> {
+        if state.connections.fetch_add(1, Ordering::SeqCst) >= state.config.max_connections {
+            state.connections.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        Some(Self(state.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub struct Server {
+    listener: TcpListener,
+    state: Arc<ServerState>,
+}
+
+impl Server {
+    pub fn bind(addr: &str, config: ServerConfig) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            state: Arc::new(ServerState {
+                config,
+                jobs: Mutex::new(HashMap::new()),
+                next_id: AtomicU64::new(1),
+                connections: AtomicUsize::new(0),
+            }),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves requests forever, each connection on its own thread, up to `ServerConfig::max_connections` at once.
+    pub fn run(self) {
+        for stream in self.listener.incoming() {
+            match stream {
+                Ok(mut stream) => {
+                    if let Err(e) = stream.set_read_timeout(Some(IO_TIMEOUT)).and_then(|_| stream.set_write_timeout(Some(IO_TIMEOUT))) {
+                        eprintln!("Error while setting the timeouts of a connection: {}", e);
+                        continue;
+                    }
+                    match ConnectionSlot::take(&self.state) {
+                        Some(slot) => {
+                            thread::spawn(move || handle_connection(stream, &slot.0));
+                        },
+                        None => {
+                            let _ = write_error(&mut stream, 503, "too many connections");
+                        },
+                    }
+                },
+                Err(e) => eprintln!("Error while accepting a connection: {}", e),
+            }
+        }
+    }
+}
+
+fn write_json(stream: &mut TcpStream, status: u16, body: &impl Serialize) -> io::Result<()> {
+    http::write_response(stream, status, "application/json", serde_json::to_string(body).unwrap().as_bytes())
+}
+
+fn write_error(stream: &mut TcpStream, status: u16, error: &str) -> io::Result<()> {
+    write_json(stream, status, &ErrorBody { error })
+}
+
+fn handle_connection(mut stream: TcpStream, state: &Arc<ServerState>) {
+    let request = match http::read_request(&stream, MAX_BODY_LEN) {
+        Ok(request) => request,
+        Err(e) => {
+            let _ = match e {
+                RequestError::BodyTooLarge => write_error(&mut stream, 413, "request body too large"),
+                RequestError::HeadTooLarge => write_error(&mut stream, 431, "request head too large"),
+                RequestError::Malformed(reason) => write_error(&mut stream, 400, reason),
+                RequestError::Io => Ok(()),
+            };
+            return;
+        },
+    };
+
+    let segments: Vec<&str> = request.path.split('/').filter(|s| !s.is_empty()).collect();
+    let res = match (request.method.as_str(), segments.as_slice()) {
+        ("OPTIONS", _) => http::write_head(&mut stream, 204, &[
+            ("Access-Control-Allow-Methods", "GET, POST, DELETE, OPTIONS"),
+            ("Access-Control-Allow-Headers", "Content-Type"),
+        ]),
+        ("POST", ["searches"]) => start_search(&mut stream, state, &request),
+        ("GET", ["searches"]) => {
+            let mut statuses: Vec<JobStatus> = state.jobs.lock().unwrap().values().map(|j| j.status.clone()).collect();
+            statuses.sort_by_key(|s| s.id);
+            write_json(&mut stream, 200, &statuses)
+        },
+        (method, ["searches", id, rest @ ..]) => match id.parse::<u64>() {
+            Err(_) => write_error(&mut stream, 404, "no such search"),
+            Ok(id) => match (method, rest) {
+                ("GET", []) => match state.jobs.lock().unwrap().get(&id) {
+                    Some(job) => write_json(&mut stream, 200, &job.status),
+                    None => write_error(&mut stream, 404, "no such search"),
+                },
+                ("GET", ["events"]) => stream_events(&mut stream, state, id),
+                ("DELETE", []) => cancel_search(&mut stream, state, id),
+                _ => write_error(&mut stream, 405, "method not allowed"),
+            },
+        },
+        _ => write_error(&mut stream, 404, "not found"),
+    };
+    if let Err(e) = res {
+        eprintln!("Error while responding to {} {}: {}", request.method, request.path, e);
+    }
+}
+
+fn start_search(stream: &mut TcpStream, state: &Arc<ServerState>, request: &Request) -> io::Result<()> {
+    let new_search: NewSearch = match serde_json::from_slice(&request.body) {
+        Ok(new_search) => new_search,
+        Err(e) => return write_error(stream, 400, format!("invalid search: {}", e).as_str()),
+    };
+
+    let mut config = state.config.search.clone();
+    config.threads = new_search.threads.unwrap_or(config.threads).clamp(1, state.config.max_threads.max(1));
+    config.constraints = Constraints {
+        waypoints: new_search.via.iter().map(|w| normalize_title(w)).collect(),
+        excluded: new_search.avoid.iter().map(|p| TitlePattern::new(p)).collect(),
+    };
+    if let Some(max_pages) = new_search.max_pages {
+        config.limits.max_processed = max_pages;
+    }
+    if let Some(max_depth) = new_search.max_depth {
+        config.limits.max_depth = max_depth;
+    }
+    if let Some(timeout) = new_search.timeout_secs {
+        if !timeout.is_finite() || timeout < 0.0 {
+            return write_error(stream, 400, "timeout_secs must be a number of seconds, 0 or more");
+        }
+        config.limits.max_duration = Some(Duration::from_secs_f64(timeout));
+    }
+
+    let mut jobs = state.jobs.lock().unwrap();
+    if jobs.values().filter(|j| j.is_running()).count() >= state.config.max_jobs {
+        return write_error(stream, 429, "too many searches are running");
+    }
+
+    let id = state.next_id.fetch_add(1, Ordering::SeqCst);
+    let cancel = Arc::new(AtomicBool::new(false));
+    let status = JobStatus {
+        id,
+        from: new_search.from.clone(),
+        to: new_search.to.clone(),
+        state: "running".to_string(),
+        message: None,
+        path: vec![],
+        pages_processed: 0,
+        in_queue: 0,
+        depth: 0,
+        dead_threads: vec![],
+        duration_secs: 0.0,
+    };
+    jobs.insert(id, Job { status: status.clone(), cancel: cancel.clone(), subscribers: vec![] });
+    drop(jobs);
+
+    let state = state.clone();
+    thread::spawn(move || run_job(&state, id, new_search.from, new_search.to, config, cancel));
+
+    write_json(stream, 201, &status)
+}
+
+/// Mirrors the progress of a search into the status of its job.
+struct JobObserver<'a> {
+    state: &'a ServerState,
+    id: u64,
+    start_instant: Instant,
+    last_event: Instant,
+}
+
+impl Observer for JobObserver<'_> {
+    fn progress(&mut self, progress: &Progress) {
+        let mut jobs = self.state.jobs.lock().unwrap();
+        let job = jobs.get_mut(&self.id).unwrap();
+        job.status.pages_processed = progress.processed;
+        job.status.in_queue = progress.in_queue;
+        job.status.depth = progress.depth;
+        job.status.duration_secs = self.start_instant.elapsed().as_secs_f64();
+        if self.last_event.elapsed() >= EVENT_INTERVAL {
+            job.broadcast("progress", &ProgressEvent {
+                pages_processed: progress.processed,
+                in_queue: progress.in_queue,
+                depth: progress.depth,
+            });
+            self.last_event = Instant::now();
+        }
+    }
+
+    fn thread_died(&mut self, thread: usize) {
+        let mut jobs = self.state.jobs.lock().unwrap();
+        let job = jobs.get_mut(&self.id).unwrap();
+        job.status.dead_threads.push(thread);
+        job.broadcast("thread_died", &thread);
+    }
+}
+
+/// Runs the search and keeps the job status up to date until it is over or cancelled.
+fn run_job(state: &ServerState, id: u64, from: String, to: String, config: SearchConfig, cancel: Arc<AtomicBool>) {
+    let observer = JobObserver {
+        state,
+        id,
+        start_instant: Instant::now(),
+        last_event: Instant::now(),
+    };
+    let report = Searcher::builder()
+        .config(config)
+        .observer(observer)
+        .cancel_flag(cancel)
+        .run(from.as_str(), to.as_str());
+
+    let mut jobs = state.jobs.lock().unwrap();
+    let job = jobs.get_mut(&id).unwrap();
+    job.status.state = report.outcome.kind().to_string();
+    job.status.message = Some(report.outcome.to_string());
+    job.status.path = report.outcome.path().to_vec();
+    job.status.duration_secs = report.duration.as_secs_f64();
+    let status = job.status.clone();
+    job.broadcast("done", &status);
+    job.subscribers.clear();
+    forget_old_jobs(&mut jobs, state.config.max_finished_jobs);
+}
+
+/// Removes the oldest finished jobs until at most `max_finished` are left.
+fn forget_old_jobs(jobs: &mut HashMap<u64, Job>, max_finished: usize) {
+    let mut finished: Vec<u64> = jobs.values().filter(|j| !j.is_running()).map(|j| j.status.id).collect();
+    if finished.len() > max_finished {
+        finished.sort_unstable();
+        for id in &finished[..finished.len() - max_finished] {
+            jobs.remove(id);
+        }
+    }
+}
+
+fn cancel_search(stream: &mut TcpStream, state: &ServerState, id: u64) -> io::Result<()> {
+    let jobs = state.jobs.lock().unwrap();
+    match jobs.get(&id) {
+        None => write_error(stream, 404, "no such search"),
+        Some(job) if !job.is_running() => write_error(stream, 409, "search is already over"),
+        Some(job) => {
+            job.cancel.store(true, Ordering::SeqCst);
+            write_json(stream, 202, &job.status)
+        },
+    }
+}
+
+fn stream_events(stream: &mut TcpStream, state: &ServerState, id: u64) -> io::Result<()> {
+    let (sender, reciever) = mpsc::channel();
+    {
+        let mut jobs = state.jobs.lock().unwrap();
+        let job = match jobs.get_mut(&id) {
+            Some(job) => job,
+            None => return write_error(stream, 404, "no such search"),
+        };
+
+        http::write_head(stream, 200, &[("Content-Type", "text/event-stream"), ("Cache-Control", "no-cache")])?;
+        if job.is_running() {
+            stream.write_all(sse_message("status", &job.status).as_bytes())?;
+            job.subscribers.push(sender);
+        }
+        else {
+            stream.write_all(sse_message("done", &job.status).as_bytes())?;
+            return stream.flush();
+        }
+    }
+    stream.flush()?;
+
+    // Ends when the job drops the sender
+    for message in reciever.iter() {
+        stream.write_all(message.as_bytes())?;
+        stream.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! Title suggestions for the search inputs, from the wiki itself or from pages seen before.
+
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    std::error::Error,
+    std::time::Duration,
+    crate::cache::LinkCache,
+    crate::search::FetchOptions,
+};
+
+/// Suggestions are no use once the user typed on, so requests wait at most this long even if `FetchOptions` allow more.
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Api endpoint of the wiki whose pages are at `wiki_url`: `https://en.wikipedia.org/wiki/` gives `https://en.wikipedia.org/w/api.php`.
+pub fn api_url(wiki_url: &str) -> String {
+    let base = wiki_url.trim_end_matches('/');
+    let base = base.strip_suffix("/wiki").unwrap_or(base);
+    format!("{}/w/api.php", base)
+}
+
+/// Titles starting with `prefix`, as the opensearch api of the wiki returns them (with spaces).
+#[cfg(not(target_arch = "wasm32"))]
+pub fn opensearch(wiki_url: &str, prefix: &str, limit: usize, fetch: &FetchOptions) -> Result<Vec<String>, Box<dyn Error>> {
+    let timeout = fetch.timeout.map_or(MAX_TIMEOUT, |t| t.min(MAX_TIMEOUT));
+    let client = FetchOptions { timeout: Some(timeout), ..fetch.clone() }.client()?;
+    let response: serde_json::Value = client.get(api_url(wiki_url))
+        .query(&[("action", "opensearch"), ("format", "json"), ("namespace", "0"), ("search", prefix), ("limit", limit.to_string().as_str())])
+        .send()?
+        .error_for_status()?
+        .json()?;
+
+    let titles = response.get(1).and_then(|t| t.as_array()).ok_or("unexpected opensearch response")?;
+    Ok(titles.iter().filter_map(|t| t.as_str()).map(str::to_string).collect())
+}
+
+/// Asks the wiki, and falls back to the titles in `cache` when it can't be reached. Titles are returned with spaces.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn suggest(wiki_url: &str, prefix: &str, cache: &LinkCache, limit: usize, fetch: &FetchOptions) -> Vec<String> {
+    opensearch(wiki_url, prefix, limit, fetch).unwrap_or_else(|_| {
+        cache.titles_with_prefix(prefix, limit).into_iter().map(|t| t.replace('_', " ")).collect()
+    })
+}
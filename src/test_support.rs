@@ -0,0 +1,228 @@
+//! Synthetic wiki served over local HTTP, so the engine can be tested without en.wikipedia.org.
+//!
+//! ```no_run
+//! use wikisearch_gui::test_support::{FakeWiki, WikiGraph};
+//! use wikisearch_gui::Searcher;
+//!
+//! let wiki = FakeWiki::start(WikiGraph::new().chain(&["A", "B", "C"]).redirect("C_page", "C"));
+//! let report = Searcher::builder().source(wiki.wiki_url()).run("A", "C");
+//! assert_eq!(report.outcome.path(), ["A", "B", "C"]);
+//! ```
+
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::http;
+
+/// What the fake wiki serves for a title.
+#[derive(Clone, Debug)]
+enum Page {
+    Links(Vec<String>),
+    /// Served as is.
+    Html(String),
+    Redirect(String),
+    /// The connection is closed without a response.
+    Broken,
+}
+
+/// Pages of the fake wiki and the links on them. Titles that are not in the graph are 404.
+#[derive(Clone, Debug, Default)]
+pub struct WikiGraph {
+    pages: HashMap<String, Page>,
+    delay: Duration,
+    page_delays: HashMap<String, Duration>,
+    /// Number of requests answered with 429 before the page is served.
+    rate_limits: HashMap<String, usize>,
+}
+
+impl WikiGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn page(mut self, title: &str, links: &[&str]) -> Self {
+        self.pages.insert(title.to_string(), Page::Links(links.iter().map(|l| l.to_string()).collect()));
+        self
+    }
+
+    /// Adds pages so that every title links to the next one. Links already on the pages are kept.
+    pub fn chain(mut self, titles: &[&str]) -> Self {
+        for pair in titles.windows(2) {
+            self.links_of(pair[0]).push(pair[1].to_string());
+        }
+        if let Some(last) = titles.last() {
+            self.links_of(last);
+        }
+        self
+    }
+
+    /// Page whose html is `html`, for checking how links are extracted.
+    pub fn html(mut self, title: &str, html: &str) -> Self {
+        self.pages.insert(title.to_string(), Page::Html(html.to_string()));
+        self
+    }
+
+    /// `from` answers with a 301 to `to`.
+    pub fn redirect(mut self, from: &str, to: &str) -> Self {
+        self.pages.insert(from.to_string(), Page::Redirect(to.to_string()));
+        self
+    }
+
+    /// Requests of `title` fail with a closed connection.
+    pub fn broken(mut self, title: &str) -> Self {
+        self.pages.insert(title.to_string(), Page::Broken);
+        self
+    }
+
+    /// Every page is served after `delay`.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// `title` is served after `delay` instead of the default delay.
+    pub fn slow(mut self, title: &str, delay: Duration) -> Self {
+        self.page_delays.insert(title.to_string(), delay);
+        self
+    }
+
+    /// The first `times` requests of `title` are answered with 429 Too Many Requests. `usize::MAX` means always.
+    pub fn rate_limited(mut self, title: &str, times: usize) -> Self {
+        self.rate_limits.insert(title.to_string(), times);
+        self
+    }
+
+    /// Links of `title`, which becomes a page of links if it is anything else.
+    fn links_of(&mut self, title: &str) -> &mut Vec<String> {
+        let page = self.pages.entry(title.to_string()).or_insert(Page::Links(vec![]));
+        if !matches!(page, Page::Links(_)) {
+            *page = Page::Links(vec![]);
+        }
+        match page {
+            Page::Links(links) => links,
+            _ => unreachable!(),
+        }
+    }
+
+    fn render(title: &str, links: &[String]) -> String {
+        let mut html = format!("<!DOCTYPE html>\n<html><head><title>{}</title></head><body>\n", title);
+        html += "<div id=\"mw-content-text\" class=\"mw-body-content\"><div class=\"mw-parser-output\">\n<p>";
+        html += title;
+        for link in links {
+            html += format!(" links to <a href=\"/wiki/{0}\" title=\"{0}\">{0}</a>,", link).as_str();
+        }
+        html += "</p>\n</div></div>\n</body></html>\n";
+        html
+    }
+}
+
+/// Serves a `WikiGraph` until dropped.
+pub struct FakeWiki {
+    addr: SocketAddr,
+    requests: Arc<AtomicUsize>,
+    /// Number of requests of every title.
+    hits: Arc<Mutex<HashMap<String, usize>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl FakeWiki {
+    pub fn start(graph: WikiGraph) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let hits = Arc::new(Mutex::new(HashMap::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let graph = Arc::new(graph);
+        let (thread_requests, thread_hits, thread_stop) = (requests.clone(), hits.clone(), stop.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                thread_requests.fetch_add(1, Ordering::SeqCst);
+                let (graph, hits) = (graph.clone(), thread_hits.clone());
+                thread::spawn(move || serve(stream, &graph, &hits));
+            }
+        });
+
+        Self { addr, requests, hits, stop }
+    }
+
+    /// Url to use as `SearchConfig::wiki_url`.
+    pub fn wiki_url(&self) -> String {
+        format!("http://{}/wiki/", self.addr)
+    }
+
+    pub fn num_of_requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+
+    pub fn num_of_requests_to(&self, title: &str) -> usize {
+        self.hits.lock().unwrap().get(title).copied().unwrap_or(0)
+    }
+}
+
+impl Drop for FakeWiki {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wakes the accept loop up
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+fn serve(mut stream: TcpStream, graph: &WikiGraph, hits: &Mutex<HashMap<String, usize>>) {
+    const HTML: &str = "text/html; charset=UTF-8";
+
+    let request = match http::read_request(&stream, 0) {
+        Ok(request) => request,
+        Err(_) => return,
+    };
+
+    if request.path == "/w/api.php" {
+        let _ = http::write_response(&mut stream, 200, "application/json; charset=utf-8", opensearch(graph, request.query.as_str()).as_bytes());
+        return;
+    }
+
+    let title = http::percent_decode(request.path.strip_prefix("/wiki/").unwrap_or(""));
+    let hit = {
+        let mut hits = hits.lock().unwrap();
+        let hit = hits.entry(title.clone()).or_insert(0);
+        *hit += 1;
+        *hit
+    };
+    thread::sleep(graph.page_delays.get(&title).copied().unwrap_or(graph.delay));
+
+    let _ = match graph.pages.get(&title) {
+        _ if graph.rate_limits.get(&title).map_or(false, |&times| hit <= times) =>
+            http::write_response(&mut stream, 429, HTML, b"<html><body>Too many requests</body></html>"),
+        Some(Page::Links(links)) => http::write_response(&mut stream, 200, HTML, WikiGraph::render(title.as_str(), links).as_bytes()),
+        Some(Page::Html(html)) => http::write_response(&mut stream, 200, HTML, html.as_bytes()),
+        Some(Page::Redirect(to)) => http::write_head(&mut stream, 301, &[("Location", format!("/wiki/{}", to).as_str()), ("Content-Length", "0")]),
+        Some(Page::Broken) => Ok(()),
+        None => http::write_response(&mut stream, 404, HTML, b"<html><body>No such page</body></html>"),
+    };
+}
+
+/// Answers `action=opensearch` like MediaWiki: `[query, titles, descriptions, urls]`, titles with spaces.
+fn opensearch(graph: &WikiGraph, query: &str) -> String {
+    let search = http::query_param(query, "search").unwrap_or_default();
+    let limit = http::query_param(query, "limit").and_then(|l| l.parse().ok()).unwrap_or(10);
+    let prefix = search.replace(' ', "_").to_lowercase();
+    let mut titles: Vec<&String> = graph.pages.keys().filter(|t| t.to_lowercase().starts_with(prefix.as_str())).collect();
+    titles.sort_unstable();
+    titles.truncate(limit);
+
+    let titles: Vec<String> = titles.into_iter().map(|t| t.replace('_', " ")).collect();
+    let descriptions = vec![""; titles.len()];
+    let urls: Vec<String> = titles.iter().map(|t| format!("/wiki/{}", t.replace(' ', "_"))).collect();
+    serde_json::json!([search, titles, descriptions, urls]).to_string()
+}
@@ -0,0 +1,187 @@
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+use std::time::Duration;
+
+use eframe::egui;
+use instant::Instant;
+
+use crate::cache::LinkCache;
+use crate::constraints::{normalize_title, title_of};
+use crate::search::{FetchOptions, PageError};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{search, suggest};
+#[cfg(target_arch = "wasm32")]
+use crate::web;
+
+/// Shorter prefixes match too many titles to be useful.
+const MIN_QUERY_LEN: usize = 2;
+const MAX_SUGGESTIONS: usize = 10;
+/// Titles are looked up once the user stopped typing for this long.
+const TYPING_PAUSE: Duration = Duration::from_millis(250);
+
+/// Text of the input and the wiki it is checked against.
+type Checked = (String, String);
+
+/// Whether the page in the input exists.
+enum Check {
+    None,
+    /// Checking the text, in the background.
+    Running(Checked, Receiver<Result<String, PageError>>),
+    Done(Checked, Result<String, PageError>),
+}
+
+impl Default for Check {
+    fn default() -> Self {
+        Check::None
+    }
+}
+
+/// Single line input for a page url or title that suggests titles as the user types, and checks that the page exists.
+/// Lookups and checks run on their own threads (futures in the web build), so a slow wiki never blocks the gui.
+#[derive(Default)]
+pub struct TitleInput {
+    /// Title in the input, as it is looked up.
+    query: String,
+    /// When `query` changed, until it is looked up.
+    typed_at: Option<Instant>,
+    /// Running lookup and its title. Results for a title that is no longer in the input are dropped.
+    pending: Option<(String, Receiver<Vec<String>>)>,
+    suggestions: Vec<String>,
+    check: Check,
+}
+
+impl TitleInput {
+    /// Starts checking `text` on the wiki at `wiki_url`, unless it is being checked or was found to exist.
+    /// Failed checks run again, the failure may have been the network. The web build fetches through the browser and ignores `fetch`.
+    pub fn check(&mut self, text: &str, wiki_url: &str, fetch: &FetchOptions) {
+        match &self.check {
+            Check::Running((t, w), _) | Check::Done((t, w), Ok(_)) if t == text && w == wiki_url => return,
+            _ => {},
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.check = Check::Running((text.to_string(), wiki_url.to_string()), receiver);
+        let (page, wiki_url) = (text.to_string(), wiki_url.to_string());
+        let fetch = fetch.clone();
+        #[cfg(not(target_arch = "wasm32"))]
+        thread::spawn(move || {
+            let _ = sender.send(search::resolve_page(page.as_str(), wiki_url.as_str(), &fetch));
+        });
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = fetch;
+            let _ = sender.send(web::resolve_page(page.as_str(), wiki_url.as_str()).await);
+        });
+    }
+
+    /// Result of the check of `text` on `wiki_url`: the title of the page after redirects. `None` while it is running.
+    pub fn checked(&self, text: &str, wiki_url: &str) -> Option<&Result<String, PageError>> {
+        match &self.check {
+            Check::Done((t, w), result) if t == text && w == wiki_url => Some(result),
+            _ => None,
+        }
+    }
+
+    /// Whether the check of `text` on `wiki_url` is still running.
+    pub fn is_checking(&self, text: &str, wiki_url: &str) -> bool {
+        matches!(&self.check, Check::Running((t, w), _) if t == text && w == wiki_url)
+    }
+
+    fn poll_check(&mut self, ctx: &egui::CtxRef) {
+        if let Check::Running(text, receiver) = &self.check {
+            match receiver.try_recv() {
+                Ok(result) => self.check = Check::Done(text.clone(), result),
+                Err(TryRecvError::Empty) => ctx.request_repaint(),
+                Err(TryRecvError::Disconnected) => self.check = Check::None,
+            }
+        }
+    }
+
+    /// Adds the input and, in the next grid cell, what the check found out.
+    pub fn ui(&mut self, ui: &mut egui::Ui, text: &mut String, wiki_url: &str, cache: &LinkCache, fetch: &FetchOptions) -> egui::Response {
+        let response = ui.add(egui::TextEdit::singleline(text));
+        let popup_id = response.id.with("suggestions");
+
+        if response.changed() {
+            self.check = Check::None;
+            let query = title_of(text.as_str()).replace('_', " ");
+            if query.chars().count() < MIN_QUERY_LEN {
+                self.query.clear();
+                self.typed_at = None;
+                self.pending = None;
+                self.suggestions.clear();
+            }
+            else if query != self.query {
+                self.query = query;
+                self.typed_at = Some(Instant::now());
+            }
+        }
+
+        if let Some(typed_at) = self.typed_at {
+            if typed_at.elapsed() < TYPING_PAUSE {
+                ui.ctx().request_repaint();
+            }
+            else {
+                self.typed_at = None;
+                let query = self.query.clone();
+                let (sender, receiver) = mpsc::channel();
+                let (wiki_url, cache, fetch) = (wiki_url.to_string(), cache.clone(), fetch.clone());
+                // The receiver is gone if the user typed on
+                #[cfg(not(target_arch = "wasm32"))]
+                thread::spawn(move || {
+                    let _ = sender.send(suggest::suggest(wiki_url.as_str(), query.as_str(), &cache, MAX_SUGGESTIONS, &fetch));
+                });
+                #[cfg(target_arch = "wasm32")]
+                wasm_bindgen_futures::spawn_local(async move {
+                    let _ = fetch;
+                    let _ = sender.send(web::suggest(wiki_url.as_str(), query.as_str(), &cache, MAX_SUGGESTIONS).await);
+                });
+                self.pending = Some((self.query.clone(), receiver));
+            }
+        }
+
+        if let Some((query, receiver)) = &self.pending {
+            match receiver.try_recv() {
+                Ok(suggestions) => {
+                    if *query == self.query {
+                        self.suggestions = suggestions;
+                        if !self.suggestions.is_empty() && response.has_focus() {
+                            ui.memory().open_popup(popup_id);
+                        }
+                    }
+                    self.pending = None;
+                },
+                // Keeps polling while nothing happens in the gui
+                Err(TryRecvError::Empty) => ui.ctx().request_repaint(),
+                Err(TryRecvError::Disconnected) => self.pending = None,
+            }
+        }
+
+        let mut picked = None;
+        egui::popup::popup_below_widget(ui, popup_id, &response, |ui| {
+            for title in &self.suggestions {
+                if ui.selectable_label(false, title.as_str()).clicked() {
+                    picked = Some(title.clone());
+                }
+            }
+        });
+        if let Some(title) = picked {
+            *text = format!("{}{}", wiki_url, normalize_title(title.as_str()));
+            self.query = title;
+            self.typed_at = None;
+            self.pending = None;
+            self.suggestions.clear();
+            self.check = Check::None;
+            ui.memory().close_popup();
+        }
+
+        self.poll_check(ui.ctx());
+        match &self.check {
+            Check::Running(..) => { ui.label("checking..."); },
+            Check::Done(_, Err(e)) => { ui.colored_label(egui::Color32::RED, e.to_string()); },
+            Check::Done(_, Ok(_)) | Check::None => { ui.label(""); },
+        }
+
+        response
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+
+use eframe::egui::{self, Align2, Color32, Pos2, Sense, Stroke, TextStyle, Vec2};
+
+use crate::search::FrontierEvent;
+
+/// Angle between consecutive children, spreads them evenly without knowing how many there will be.
+const GOLDEN_ANGLE: f32 = 2.399_963;
+/// Distance between depth levels, in points at zoom 1.
+const LEVEL_DISTANCE: f32 = 80.0;
+
+const DEPTH_COLORS: [Color32; 6] = [
+    Color32::from_rgb(230, 230, 230),
+    Color32::from_rgb(100, 160, 255),
+    Color32::from_rgb(80, 200, 120),
+    Color32::from_rgb(230, 190, 60),
+    Color32::from_rgb(200, 110, 220),
+    Color32::from_rgb(120, 210, 210),
+];
+
+struct Node {
+    title: String,
+    parent: Option<usize>,
+    depth: usize,
+    children: usize,
+    /// Position around the root, in points at zoom 1.
+    pos: Vec2,
+    angle: f32,
+}
+
+/// The BFS tree as it grows, drawn as rings around the start page.
+pub struct TreeView {
+    nodes: Vec<Node>,
+    index: HashMap<String, usize>,
+    /// Page every busy thread is fetching.
+    fetching: HashMap<usize, String>,
+    path: Vec<usize>,
+    /// Number of discovered pages, including the ones that are not drawn.
+    discovered: usize,
+    max_nodes: usize,
+    zoom: f32,
+    pan: Vec2,
+}
+
+impl Default for TreeView {
+    fn default() -> Self {
+        Self::new(2000)
+    }
+}
+
+impl TreeView {
+    /// Draws at most `max_nodes` pages, the ones discovered first.
+    pub fn new(max_nodes: usize) -> Self {
+        Self {
+            nodes: Vec::new(),
+            index: HashMap::new(),
+            fetching: HashMap::new(),
+            path: Vec::new(),
+            discovered: 0,
+            max_nodes,
+            zoom: 1.0,
+            pan: Vec2::ZERO,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn add_node(&mut self, title: &str, parent: Option<usize>, depth: usize) -> usize {
+        let (pos, angle) = match parent {
+            None if self.nodes.is_empty() => (Vec2::ZERO, 0.0),
+            // Start of a later leg that was not discovered before
+            None => (Vec2::angled(self.nodes.len() as f32 * GOLDEN_ANGLE) * LEVEL_DISTANCE, 0.0),
+            Some(p) => {
+                let parent = &mut self.nodes[p];
+                parent.children += 1;
+                // Children fan out around the direction of the parent, in a narrower fan the deeper they are
+                let spread = if parent.depth == 0 { TAU } else { TAU / 3.0 / parent.depth as f32 };
+                let offset = (parent.children as f32 * GOLDEN_ANGLE) % TAU / TAU - 0.5;
+                let angle = parent.angle + offset * spread;
+                let radius = (parent.depth + 1) as f32 * LEVEL_DISTANCE;
+                (Vec2::angled(angle) * radius, angle)
+            },
+        };
+        self.nodes.push(Node { title: title.to_string(), parent, depth, children: 0, pos, angle });
+        self.index.insert(title.to_string(), self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    pub fn add(&mut self, event: FrontierEvent) {
+        match event {
+            FrontierEvent::Discovered { title, parent, depth } => {
+                self.discovered += 1;
+                if self.nodes.len() >= self.max_nodes || self.index.contains_key(&title) {
+                    return;
+                }
+                let parent = match parent {
+                    Some(parent) => match self.index.get(&parent) {
+                        Some(&p) => Some(p),
+                        None => return,
+                    },
+                    None => None,
+                };
+                self.add_node(title.as_str(), parent, depth);
+            },
+            FrontierEvent::Fetching { thread, title } => {
+                self.fetching.insert(thread, title);
+            },
+            FrontierEvent::Fetched { thread } => {
+                self.fetching.remove(&thread);
+            },
+        }
+    }
+
+    /// Highlights the path. Pages of the path that were not drawn because of the cap are added.
+    pub fn set_path(&mut self, path: &[String]) {
+        self.fetching.clear();
+        self.path.clear();
+        for (i, title) in path.iter().enumerate() {
+            let node = match self.index.get(title) {
+                Some(&node) => node,
+                None => {
+                    let parent = if i > 0 { self.path.last().copied() } else { None };
+                    self.add_node(title.as_str(), parent, i)
+                },
+            };
+            self.path.push(node);
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if self.discovered > self.nodes.len() {
+                ui.label(format!("Showing {} of {} pages", self.nodes.len(), self.discovered));
+            }
+            else {
+                ui.label(format!("{} pages", self.nodes.len()));
+            }
+            ui.label("(drag to pan, scroll to zoom)");
+            if ui.button("Reset view").clicked() {
+                self.zoom = 1.0;
+                self.pan = Vec2::ZERO;
+            }
+        });
+
+        let size = Vec2::new(ui.available_width(), ui.available_height().max(300.0));
+        let (response, painter) = ui.allocate_painter(size, Sense::drag());
+        self.pan += response.drag_delta();
+        if response.hovered() {
+            let scroll = ui.input().scroll_delta.y;
+            if scroll != 0.0 {
+                let zoom = (self.zoom * (1.0 + scroll * 0.002)).clamp(0.05, 10.0);
+                // Keep the point under the pointer in place
+                if let Some(pointer) = response.hover_pos() {
+                    let from_center = pointer - response.rect.center() - self.pan;
+                    self.pan -= from_center * (zoom / self.zoom - 1.0);
+                }
+                self.zoom = zoom;
+            }
+        }
+
+        let center = response.rect.center() + self.pan;
+        let to_screen = |node: &Node| -> Pos2 { center + node.pos * self.zoom };
+
+        painter.rect_filled(response.rect, 0.0, Color32::from_gray(20));
+        for node in &self.nodes {
+            if let Some(p) = node.parent {
+                painter.line_segment([to_screen(&self.nodes[p]), to_screen(node)], Stroke::new(1.0, Color32::from_gray(70)));
+            }
+        }
+        for pair in self.path.windows(2) {
+            painter.line_segment([to_screen(&self.nodes[pair[0]]), to_screen(&self.nodes[pair[1]])], Stroke::new(3.0, Color32::RED));
+        }
+
+        let radius = (3.0 * self.zoom.sqrt()).max(1.5);
+        for node in &self.nodes {
+            painter.circle_filled(to_screen(node), radius, DEPTH_COLORS[node.depth % DEPTH_COLORS.len()]);
+        }
+        for title in self.fetching.values() {
+            if let Some(&i) = self.index.get(title) {
+                let pos = to_screen(&self.nodes[i]);
+                painter.circle_stroke(pos, radius + 3.0, Stroke::new(2.0, Color32::YELLOW));
+                painter.text(pos + Vec2::new(radius + 5.0, 0.0), Align2::LEFT_CENTER, title, TextStyle::Small, Color32::YELLOW);
+            }
+        }
+        for &i in &self.path {
+            let pos = to_screen(&self.nodes[i]);
+            painter.circle_filled(pos, radius + 2.0, Color32::RED);
+            painter.text(pos + Vec2::new(radius + 5.0, 0.0), Align2::LEFT_CENTER, self.nodes[i].title.as_str(), TextStyle::Body, Color32::WHITE);
+        }
+
+        // Title of the page under the pointer
+        if let Some(pointer) = response.hover_pos() {
+            let nearest = self.nodes.iter()
+                .map(|n| (n, to_screen(n).distance(pointer)))
+                .filter(|(_, d)| *d <= radius + 3.0)
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            if let Some((node, _)) = nearest {
+                painter.text(pointer + Vec2::new(10.0, -10.0), Align2::LEFT_BOTTOM, format!("{} (depth {})", node.title, node.depth), TextStyle::Body, Color32::WHITE);
+            }
+        }
+    }
+}
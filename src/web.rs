@@ -0,0 +1,373 @@
+//! Searches for the web build. Browsers can neither block nor start threads, so pages are fetched
+//! through the MediaWiki api of the wiki (with `origin=*` for CORS) and concurrent requests are futures.
+//!
+//! Titles are returned the way the threaded engine returns them: as they appear in links, with underscores and percent-encoding.
+//! Timeout, User-Agent and proxy of `FetchOptions` are up to the browser.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem::swap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use futures_util::future::join_all;
+use instant::Instant;
+use reqwest::Client;
+use serde_json::Value;
+use wasm_bindgen::JsValue;
+
+use crate::archive::ArchiveMode;
+use crate::cache::LinkCache;
+use crate::constraints::normalize_title;
+use crate::context::{self, LinkContext};
+use crate::first_link::{first_link, ChainEnd, FirstLinkChain};
+use crate::graph::{ExploredGraph, GraphRecording};
+use crate::http::percent_decode;
+use crate::search::{self, FrontierEvent, PageError, SearchConfig, SearchOutcome};
+use crate::suggest::api_url;
+
+/// Pages processed without waiting for the network before the browser gets a chance to draw.
+const PAGES_PER_YIELD: usize = 100;
+
+/// Resolves after `duration`, through a timer of the browser.
+async fn sleep(duration: Duration) {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        match web_sys::window() {
+            Some(window) => {
+                let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, duration.as_millis() as i32);
+            },
+            None => {
+                let _ = resolve.call0(&JsValue::NULL);
+            },
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+/// Api request to the wiki whose pages are at `wiki_url`, with the parameters every request needs.
+async fn api(client: &Client, wiki_url: &str, params: &[(&str, &str)]) -> Result<Value, String> {
+    client.get(api_url(wiki_url))
+        .query(&[("format", "json"), ("formatversion", "2"), ("origin", "*")])
+        .query(params)
+        .send().await.map_err(|e| e.to_string())?
+        .error_for_status().map_err(|e| e.to_string())?
+        .json().await.map_err(|e| e.to_string())
+}
+
+/// The api takes and returns titles with spaces.
+fn api_title(title: &str) -> String {
+    percent_decode(title).replace('_', " ")
+}
+
+/// Article links on the page, following the api continuation. A missing page has none.
+async fn page_links(client: &Client, wiki_url: &str, title: &str) -> Result<Vec<String>, String> {
+    let title = api_title(title);
+    let mut links = Vec::new();
+    let mut next: Option<String> = None;
+    loop {
+        let mut params = vec![("action", "query"), ("prop", "links"), ("titles", title.as_str()), ("plnamespace", "0"), ("pllimit", "max"), ("redirects", "1")];
+        if let Some(next) = &next {
+            params.push(("plcontinue", next.as_str()));
+        }
+        let response = api(client, wiki_url, &params).await?;
+        for page in response["query"]["pages"].as_array().into_iter().flatten() {
+            links.extend(page["links"].as_array().into_iter().flatten().filter_map(|l| l["title"].as_str()).map(normalize_title));
+        }
+        match response["continue"]["plcontinue"].as_str() {
+            Some(plcontinue) => next = Some(plcontinue.to_string()),
+            None => return Ok(links),
+        }
+    }
+}
+
+/// Same as `search::resolve_page`.
+pub async fn resolve_page(page: &str, wiki_url: &str) -> Result<String, PageError> {
+    let title = search::page_title(page, wiki_url)?;
+    let response = api(&Client::new(), wiki_url, &[("action", "query"), ("titles", api_title(title.as_str()).as_str()), ("redirects", "1")]).await
+        .map_err(PageError::Network)?;
+    let page = &response["query"]["pages"][0];
+    if page["missing"].as_bool().unwrap_or(false) || page["invalid"].as_bool().unwrap_or(false) {
+        return Err(PageError::Missing);
+    }
+    Ok(page["title"].as_str().map_or(title, normalize_title))
+}
+
+/// Same as `suggest::suggest`.
+pub async fn suggest(wiki_url: &str, prefix: &str, cache: &LinkCache, limit: usize) -> Vec<String> {
+    let limit_param = limit.to_string();
+    let response = api(&Client::new(), wiki_url, &[("action", "opensearch"), ("namespace", "0"), ("search", prefix), ("limit", limit_param.as_str())]).await;
+    match response.as_ref().ok().and_then(|r| r[1].as_array()) {
+        Some(titles) => titles.iter().filter_map(|t| t.as_str()).map(str::to_string).collect(),
+        None => cache.titles_with_prefix(prefix, limit).into_iter().map(|t| t.replace('_', " ")).collect(),
+    }
+}
+
+/// Same as `first_link::follow_first_links`, the page html comes from the parse api.
+pub async fn follow_first_links(from: &str, to: &str, wiki_url: &str, max_steps: usize, num_of_links_sender: Sender<(usize, usize, usize)>) -> FirstLinkChain {
+    let client = Client::new();
+
+    let to = normalize_title(to);
+    let mut chain = vec![normalize_title(from)];
+    let mut seen = HashMap::new();
+    seen.insert(chain[0].clone(), 0usize);
+
+    loop {
+        let current = chain.last().unwrap().clone();
+        if current == to {
+            return FirstLinkChain { chain, end: ChainEnd::Target };
+        }
+        if max_steps > 0 && chain.len() > max_steps {
+            return FirstLinkChain { chain, end: ChainEnd::StepLimit };
+        }
+
+        let page = api_title(current.as_str());
+        let html = match api(&client, wiki_url, &[("action", "parse"), ("page", page.as_str()), ("prop", "text"), ("redirects", "1")]).await {
+            Ok(response) => response["parse"]["text"].as_str().unwrap_or_default().to_string(),
+            Err(e) => return FirstLinkChain { chain, end: ChainEnd::Error(e) },
+        };
+
+        if num_of_links_sender.send((chain.len(), 0, chain.len() - 1)).is_err() {
+            return FirstLinkChain { chain, end: ChainEnd::Cancelled };
+        }
+
+        // The parse api leaves out the content div the link finder starts from
+        let next = match first_link(format!("<div id=\"mw-content-text\">{}</div>", html).as_str()) {
+            Some(next) => next,
+            None => return FirstLinkChain { chain, end: ChainEnd::DeadEnd },
+        };
+
+        if let Some(&i) = seen.get(&next) {
+            return FirstLinkChain { chain, end: ChainEnd::Loop(i) };
+        }
+        seen.insert(next.clone(), chain.len());
+        chain.push(next);
+    }
+}
+
+/// Same as `context::path_context`. The links api has no html, so every page of the path is fetched with the parse api,
+/// except in a replay, which takes the context from the archive.
+pub async fn path_context(path: &[String], config: &SearchConfig) -> Result<Vec<LinkContext>, String> {
+    let client = Client::new();
+    let mut res = Vec::new();
+    for hop in path.windows(2) {
+        let missing = LinkContext { from: hop[0].clone(), to: hop[1].clone(), ..Default::default() };
+        if let ArchiveMode::Replay(archive) = &config.archive {
+            res.push(archive.context(hop[0].as_str()).into_iter().find(|c| c.to == hop[1]).unwrap_or(missing));
+            continue;
+        }
+        sleep(config.rate_limiter.reserve()).await;
+        let page = api_title(hop[0].as_str());
+        let response = api(&client, config.wiki_url.as_str(), &[("action", "parse"), ("page", page.as_str()), ("prop", "text"), ("redirects", "1")]).await
+            .map_err(|e| format!("Error while fetching {}: {}", hop[0], e))?;
+        // The parse api leaves out the content div the link scan starts from
+        let html = format!("<div id=\"mw-content-text\">{}</div>", response["parse"]["text"].as_str().unwrap_or_default());
+        res.push(context::link_context(html.as_str(), hop[0].as_str(), hop[1].as_str()).unwrap_or(missing));
+    }
+    Ok(res)
+}
+
+/// State shared by all the legs of one search.
+struct WebSearch<'a> {
+    client: Client,
+    config: &'a SearchConfig,
+    deadline: Option<Instant>,
+    num_of_links_sender: &'a Sender<(usize, usize, usize)>,
+    frontier_sender: &'a Sender<FrontierEvent>,
+    cancel_flag: &'a AtomicBool,
+    processed: usize,
+    since_yield: usize,
+    leg: usize,
+    graph: ExploredGraph,
+}
+
+impl WebSearch<'_> {
+    /// Whether anybody still waits for the search.
+    fn send_progress(&self, in_queue: usize, depth: usize) -> bool {
+        !self.cancel_flag.load(Ordering::SeqCst) && self.num_of_links_sender.send((self.processed, in_queue, depth)).is_ok()
+    }
+
+    fn frontier(&self, event: FrontierEvent) {
+        let _ = self.frontier_sender.send(event);
+    }
+
+    fn discovered(&mut self, title: &str, parent: Option<&str>, depth: usize) {
+        if self.config.graph_recording != GraphRecording::Off {
+            self.graph.add_node(title, self.leg, depth, parent);
+        }
+        self.frontier(FrontierEvent::Discovered { title: title.to_string(), parent: parent.map(str::to_string), depth });
+    }
+
+    /// Same messages as the threaded engine.
+    fn limit_reached(&self, in_queue: usize) -> Option<SearchOutcome> {
+        let limits = &self.config.limits;
+        if limits.max_in_queue > 0 && in_queue >= limits.max_in_queue {
+            return Some(SearchOutcome::LimitReached(format!("more than {} links in the queue", limits.max_in_queue)));
+        }
+        if limits.max_processed > 0 && self.processed >= limits.max_processed {
+            return Some(SearchOutcome::LimitReached(format!("{} pages processed", limits.max_processed)));
+        }
+        if self.deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            return Some(SearchOutcome::LimitReached(format!("search took longer than {}s", limits.max_duration.unwrap().as_secs_f32())));
+        }
+        None
+    }
+
+    /// Links of the page from the cache or the wiki, or only from the archive in a replay. A failed request is tried again `worker_restarts` times.
+    async fn links(&self, title: &str) -> Result<Vec<String>, String> {
+        let links = match &self.config.archive {
+            ArchiveMode::Replay(archive) => return Ok(archive.get(title).unwrap_or_default()),
+            _ => self.fetch_links(title).await?,
+        };
+        if let ArchiveMode::Record(archive) = &self.config.archive {
+            archive.insert(title, &links);
+        }
+        Ok(links)
+    }
+
+    async fn fetch_links(&self, title: &str) -> Result<Vec<String>, String> {
+        if let Some(links) = self.config.cache.get(title) {
+            return Ok(links);
+        }
+        let mut attempt = 0;
+        loop {
+            sleep(self.config.rate_limiter.reserve()).await;
+            match page_links(&self.client, self.config.wiki_url.as_str(), title).await {
+                Ok(links) => {
+                    self.config.cache.insert(title, &links);
+                    return Ok(links);
+                },
+                Err(e) if attempt >= self.config.worker_restarts => return Err(format!("Error while fetching {}: {}", title, e)),
+                Err(_) => attempt += 1,
+            }
+        }
+    }
+
+    /// BFS from `from` to `to` that fetches up to `config.threads` pages at once. Pages for which `is_excluded` returns true are never expanded.
+    async fn search_leg(&mut self, from: &str, to: &str, is_excluded: impl Fn(&str) -> bool) -> SearchOutcome {
+        if from == to {
+            return SearchOutcome::Found(vec![from.to_string()]);
+        }
+        self.discovered(from, None, 0);
+
+        let mut parents = HashMap::new();
+        parents.insert(from.to_string(), String::new());
+        let mut level = VecDeque::from(vec![from.to_string()]);
+        let mut next_level = VecDeque::new();
+        let mut depth = 0usize;
+
+        loop {
+            while !level.is_empty() {
+                let in_queue = level.len() + next_level.len();
+                if !self.send_progress(in_queue, depth) {
+                    return SearchOutcome::Cancelled;
+                }
+                if let Some(outcome) = self.limit_reached(in_queue) {
+                    return outcome;
+                }
+
+                let batch: Vec<String> = level.drain(..level.len().min(self.config.threads.max(1))).collect();
+                for (i, title) in batch.iter().enumerate() {
+                    self.frontier(FrontierEvent::Fetching { thread: i, title: title.clone() });
+                }
+                let results = join_all(batch.iter().map(|title| self.links(title))).await;
+
+                for (i, (parent, links)) in batch.iter().zip(results).enumerate() {
+                    self.frontier(FrontierEvent::Fetched { thread: i });
+                    let links = match links {
+                        Ok(links) => links,
+                        Err(e) => return SearchOutcome::Error(e),
+                    };
+                    self.processed += 1;
+
+                    for link in links {
+                        if self.config.graph_recording == GraphRecording::AllLinks {
+                            self.graph.add_link(parent.as_str(), link.as_str());
+                        }
+                        if link == to {
+                            self.discovered(link.as_str(), Some(parent.as_str()), depth + 1);
+                            let mut path = vec![link, parent.clone()];
+                            while path.last().unwrap() != from {
+                                path.push(parents[path.last().unwrap()].clone());
+                            }
+                            path.reverse();
+                            return SearchOutcome::Found(path);
+                        }
+                        if !parents.contains_key(&link) && !is_excluded(link.as_str()) {
+                            self.discovered(link.as_str(), Some(parent.as_str()), depth + 1);
+                            parents.insert(link.clone(), parent.clone());
+                            next_level.push_back(link);
+                        }
+                    }
+                }
+
+                // Pages from the cache never wait, the browser would freeze without this
+                self.since_yield += batch.len();
+                if self.since_yield >= PAGES_PER_YIELD {
+                    self.since_yield = 0;
+                    sleep(Duration::ZERO).await;
+                }
+            }
+
+            if next_level.is_empty() {
+                return SearchOutcome::NoPath;
+            }
+            swap(&mut level, &mut next_level);
+            depth += 1;
+
+            if self.config.limits.max_depth > 0 && depth >= self.config.limits.max_depth {
+                return SearchOutcome::LimitReached(format!("no path of {} links or less", self.config.limits.max_depth));
+            }
+        }
+    }
+}
+
+/// Same as `search::search`, with the legs through the waypoints. Sends (num_of_processed, num_in_queue, search_depth)
+/// and the frontier of the search as it goes, and stops soon after `cancel_flag` is set or `num_of_links_sender` is closed.
+/// Returns the explored graph along with the outcome, as recorded by `config.graph_recording`.
+pub async fn search(from: &str, to: &str, config: &SearchConfig, num_of_links_sender: &Sender<(usize, usize, usize)>,
+        frontier_sender: &Sender<FrontierEvent>, cancel_flag: &AtomicBool) -> (SearchOutcome, ExploredGraph) {
+    let mut ctx = WebSearch {
+        client: Client::new(),
+        config,
+        deadline: config.limits.max_duration.map(|d| Instant::now() + d),
+        num_of_links_sender,
+        frontier_sender,
+        cancel_flag,
+        processed: 0,
+        since_yield: 0,
+        leg: 0,
+        graph: ExploredGraph::default(),
+    };
+
+    let constraints = &config.constraints;
+    let mut stops = vec![normalize_title(from)];
+    stops.extend(constraints.waypoints.iter().map(|w| normalize_title(w)));
+    stops.push(normalize_title(to));
+
+    let outcome = search_legs(&stops, &mut ctx).await;
+    ctx.graph.path = outcome.path().to_vec();
+    (outcome, ctx.graph)
+}
+
+async fn search_legs(stops: &[String], ctx: &mut WebSearch<'_>) -> SearchOutcome {
+    let config = ctx.config;
+    let constraints = &config.constraints;
+    let mut path: Vec<String> = vec![];
+    for (i, leg) in stops.windows(2).enumerate() {
+        ctx.leg = i;
+        let mut on_path: HashSet<String> = path.iter().cloned().collect();
+        on_path.extend(stops[i + 2..].iter().filter(|s| **s != leg[1]).cloned());
+        let leg_path = match ctx.search_leg(leg[0].as_str(), leg[1].as_str(), |title| constraints.is_excluded(title) || on_path.contains(title)).await {
+            SearchOutcome::Found(leg_path) => leg_path,
+            outcome => return outcome,
+        };
+
+        if path.is_empty() {
+            path = leg_path;
+        }
+        else {
+            path.extend(leg_path.into_iter().skip(1));
+        }
+    }
+    SearchOutcome::Found(path)
+}
@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use wikisearch_gui::batch::{completed_pairs, read_pairs, run_batch, ReportFormat, ReportWriter, SearchRecord};
+use wikisearch_gui::test_support::{FakeWiki, WikiGraph};
+use wikisearch_gui::SearchConfig;
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("wikisearch-batch-{}-{}", std::process::id(), name))
+}
+
+fn record(from: &str, to: &str, outcome: &str) -> SearchRecord {
+    SearchRecord {
+        from: from.to_string(),
+        to: to.to_string(),
+        outcome: outcome.to_string(),
+        message: "Path of 1 link found, or \"so\" it says".to_string(),
+        length: Some(1),
+        pages_processed: 1,
+        duration_secs: 0.5,
+        path: vec![from.to_string(), to.to_string()],
+        context: vec![],
+    }
+}
+
+fn pairs(list: &[(&str, &str)]) -> HashSet<(String, String)> {
+    list.iter().map(|(from, to)| (from.to_string(), to.to_string())).collect()
+}
+
+#[test]
+fn reads_pairs_in_csv_and_tsv() {
+    let path = temp_file("pairs.csv");
+    fs::write(&path, "from,to\n# comment\n\nStart,Goal\n\"Hello, World\",\"Say \"\"hi\"\"\"\nNew York\tBig Apple\n").unwrap();
+    assert_eq!(read_pairs(&path).unwrap(), vec![
+        ("Start".to_string(), "Goal".to_string()),
+        ("Hello, World".to_string(), "Say \"hi\"".to_string()),
+        ("New York".to_string(), "Big Apple".to_string()),
+    ]);
+
+    fs::write(&path, "Start,Goal\nLonely\n").unwrap();
+    assert!(read_pairs(&path).unwrap_err().to_string().contains("line 2"));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn csv_quoting_round_trips() {
+    let path = temp_file("quoting.csv");
+    let mut report = ReportWriter::open(&path, ReportFormat::Csv, false).unwrap();
+    report.write(&record("Hello, World", "Say \"hi\"", "found")).unwrap();
+    report.write(&record("Two\nlines", "Goal", "no_path")).unwrap();
+    drop(report);
+
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+    assert_eq!(completed_pairs(&path, ReportFormat::Csv).unwrap(), pairs(&[("Hello, World", "Say \"hi\""), ("Two lines", "Goal")]));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn resume_drops_a_cut_off_last_line_and_retries_failures() {
+    for (name, format) in [("resume.csv", ReportFormat::Csv), ("resume.jsonl", ReportFormat::Json)] {
+        let path = temp_file(name);
+        let mut report = ReportWriter::open(&path, format, false).unwrap();
+        report.write(&record("A", "B", "found")).unwrap();
+        report.write(&record("C", "D", "error")).unwrap();
+        report.write(&record("E", "F", "cancelled")).unwrap();
+        report.write(&record("G", "H", "limit_reached")).unwrap();
+        drop(report);
+
+        // An interrupted run, in the middle of the next record
+        let mut cut = fs::read_to_string(&path).unwrap();
+        let last_line = cut.trim_end().rsplit('\n').next().unwrap().to_string();
+        cut.push_str(&last_line.replace('G', "I")[..last_line.len() / 2]);
+        fs::write(&path, cut).unwrap();
+
+        assert_eq!(completed_pairs(&path, format).unwrap(), pairs(&[("A", "B"), ("G", "H")]), "{}", name);
+
+        let mut report = ReportWriter::open(&path, format, true).unwrap();
+        report.write(&record("I", "J", "found")).unwrap();
+        drop(report);
+        assert_eq!(completed_pairs(&path, format).unwrap(), pairs(&[("A", "B"), ("G", "H"), ("I", "J")]), "{}", name);
+        assert!(fs::read_to_string(&path).unwrap().ends_with('\n'));
+        fs::remove_file(&path).unwrap();
+    }
+
+    assert!(completed_pairs(&temp_file("missing.csv"), ReportFormat::Csv).unwrap().is_empty());
+}
+
+#[test]
+fn runs_the_pairs_that_are_not_done() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "Middle", "Goal"])
+        .page("Island", &[]));
+    let config = SearchConfig { wiki_url: wiki.wiki_url(), ..Default::default() };
+    let list = vec![
+        ("Start".to_string(), "Goal".to_string()),
+        ("Start".to_string(), "Island".to_string()),
+        ("Middle".to_string(), "Goal".to_string()),
+    ];
+
+    let path = temp_file("run.jsonl");
+    let mut report = ReportWriter::open(&path, ReportFormat::Json, false).unwrap();
+    let mut seen = vec![];
+    let skip = pairs(&[("Middle", "Goal")]);
+    let searches = run_batch(&list, &config, &skip, &mut report, |i, record| seen.push((i, record.outcome.clone()))).unwrap();
+    drop(report);
+
+    assert_eq!(searches, 2);
+    assert_eq!(seen, vec![(0, "found".to_string()), (1, "no_path".to_string())]);
+    let records: Vec<SearchRecord> = fs::read_to_string(&path).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(records[0].path, vec!["Start", "Middle", "Goal"]);
+    assert_eq!(records[0].length, Some(2));
+    assert_eq!(completed_pairs(&path, ReportFormat::Json).unwrap(), pairs(&[("Start", "Goal"), ("Start", "Island")]));
+    fs::remove_file(&path).unwrap();
+}
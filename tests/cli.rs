@@ -0,0 +1,71 @@
+use std::process::{Command, Output};
+
+use wikisearch_gui::test_support::{FakeWiki, WikiGraph};
+
+fn cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_wikisearch-cli")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[test]
+fn rejects_bad_arguments() {
+    let output = cli(&["--help"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).starts_with("Usage:"));
+
+    for (args, error) in [
+        (&[][..], "Expected exactly two pages"),
+        (&["Start"][..], "Expected exactly two pages"),
+        (&["--frobnicate", "Start", "Goal"][..], "Unknown option: --frobnicate"),
+        (&["-t", "0", "Start", "Goal"][..], "At least one thread"),
+        (&["-t", "many", "Start", "Goal"][..], "Invalid value for -t: many"),
+        (&["--timeout"][..], "Missing value for --timeout"),
+        (&["--timeout", "-1", "Start", "Goal"][..], "Invalid value for --timeout"),
+        (&["--timeout", "inf", "Start", "Goal"][..], "Invalid value for --timeout"),
+        (&["--timeout", "NaN", "Start", "Goal"][..], "Invalid value for --timeout"),
+        (&["--request-timeout", "-5", "Start", "Goal"][..], "Invalid value for --request-timeout"),
+        (&["--max-rate", "NaN", "Start", "Goal"][..], "Invalid value for --max-rate"),
+        (&["batch", "pairs.csv"][..], "Batch mode requires --report"),
+    ] {
+        let output = cli(args);
+        assert_eq!(output.status.code(), Some(3), "{:?}", args);
+        assert!(stderr(&output).contains(error), "{:?}: {}", args, stderr(&output));
+    }
+}
+
+#[test]
+fn exit_code_follows_the_outcome() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "Middle", "Goal"])
+        .page("Island", &[]));
+    let wiki_url = wiki.wiki_url();
+    let search = |args: &[&str]| cli(&[&["-q", "--wiki-url", wiki_url.as_str()], args].concat());
+
+    let output = search(&["Start", "Goal"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "Start\nMiddle\nGoal\n");
+
+    let output = search(&["--json", "Start", "Goal"]);
+    let record: serde_json::Value = serde_json::from_str(stdout(&output).as_str()).unwrap();
+    assert_eq!(record["length"], 2);
+
+    assert_eq!(search(&["Start", "Island"]).status.code(), Some(1));
+    assert_eq!(search(&["--max-depth", "1", "Start", "Goal"]).status.code(), Some(2));
+    assert_eq!(search(&["--timeout", "0", "Start", "Goal"]).status.code(), Some(2));
+
+    let export = std::env::temp_dir().join(format!("wikisearch-cli-{}.graphml", std::process::id()));
+    assert_eq!(search(&["--export", export.to_str().unwrap(), "Start", "Goal"]).status.code(), Some(0));
+    assert!(std::fs::read_to_string(&export).unwrap().contains("<graphml"));
+    std::fs::remove_file(&export).unwrap();
+
+    let output = search(&["--export", "/nonexistent/dir/graph.dot", "Start", "Goal"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("Error while writing /nonexistent/dir/graph.dot"));
+}
@@ -0,0 +1,69 @@
+use wikisearch_gui::constraints::{join_list, normalize_title, title_of, Constraints, TitlePattern};
+
+#[test]
+fn takes_titles_out_of_urls() {
+    assert_eq!(title_of("https://en.wikipedia.org/wiki/Rust_(programming_language)"), "Rust_(programming_language)");
+    assert_eq!(title_of("https://en.wikipedia.org/wiki/AC/DC"), "AC/DC");
+    assert_eq!(title_of("http://127.0.0.1:8000/wiki/Goal"), "Goal");
+    assert_eq!(title_of("https://example.org/pages/Goal"), "https://example.org/pages/Goal");
+    assert_eq!(title_of("/wiki/AC/DC"), "AC/DC");
+    assert_eq!(title_of("  AC/DC "), "AC/DC");
+    assert_eq!(title_of("New York"), "New York");
+}
+
+#[test]
+fn normalizes_titles_like_links() {
+    assert_eq!(normalize_title("New York"), "New_York");
+    assert_eq!(normalize_title("AC/DC"), "AC/DC");
+    assert_eq!(normalize_title("https://en.wikipedia.org/wiki/AC/DC"), "AC/DC");
+    assert_eq!(normalize_title("Zürich"), "Z%C3%BCrich");
+    assert_eq!(normalize_title("Z%C3%BCrich"), "Z%C3%BCrich");
+    assert_eq!(normalize_title("C++"), "C%2B%2B");
+    assert_eq!(normalize_title("Rust (programming language)"), "Rust_(programming_language)");
+    assert_eq!(normalize_title("100% (album)"), "100%25_(album)");
+    assert_eq!(normalize_title("100%25_(album)"), "100%25_(album)");
+}
+
+#[test]
+fn matches_title_patterns() {
+    let pattern = |p: &str| TitlePattern::new(p);
+    assert!(pattern("Goal").matches("Goal"));
+    assert!(!pattern("Goal").matches("Goals"));
+    assert!(pattern("List_of_*").matches("List_of_lists"));
+    assert!(pattern("List of *").matches("List_of_lists"));
+    assert!(!pattern("List_of_*").matches("Lists"));
+    assert!(pattern("*_(disambiguation)").matches("Mercury_(disambiguation)"));
+    assert!(pattern("*a*b*").matches("xaxxbx"));
+    assert!(!pattern("*a*b").matches("xaxxbx"));
+    assert!(pattern("?oal").matches("Goal"));
+    assert!(!pattern("?oal").matches("oal"));
+    assert!(pattern("####").matches("1984"));
+    assert!(!pattern("####").matches("19a4"));
+    assert!(pattern("*").matches(""));
+}
+
+#[test]
+fn patterns_are_normalized_like_titles() {
+    assert!(TitlePattern::new("New York").matches("New_York"));
+    assert!(TitlePattern::new("https://en.wikipedia.org/wiki/New_York").matches("New_York"));
+    assert!(TitlePattern::new("Zürich").matches("Z%C3%BCrich"));
+    assert!(TitlePattern::new("Z%C3%BCrich").matches("Z%C3%BCrich"));
+    assert!(TitlePattern::new("Z?rich").matches("Z%C3%BCrich"));
+    assert!(TitlePattern::new("AC/*").matches("AC/DC"));
+
+    let constraints = Constraints::parse("", "New York, Zürich");
+    assert!(constraints.is_excluded("New_York"));
+    assert!(constraints.is_excluded("Z%C3%BCrich"));
+    assert!(!constraints.is_excluded("Zurich"));
+}
+
+#[test]
+fn parses_quoted_list_items() {
+    let constraints = Constraints::parse("\"Washington, D.C.\", Boston\nNew York", "\"Say \"\"Hi\"\", maybe\"");
+    assert_eq!(constraints.waypoints, ["Washington,_D.C.", "Boston", "New_York"]);
+    assert!(constraints.is_excluded("Say_%22Hi%22,_maybe"));
+
+    let list = join_list(["Washington, D.C.", "Boston", "Say \"Hi\""]);
+    assert_eq!(list, "\"Washington, D.C.\", Boston, \"Say \"\"Hi\"\"\"");
+    assert_eq!(Constraints::parse(&list, "").waypoints, ["Washington,_D.C.", "Boston", "Say_%22Hi%22"]);
+}
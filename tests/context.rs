@@ -0,0 +1,99 @@
+use wikisearch_gui::archive::{Archive, ArchiveMode};
+use wikisearch_gui::context::{link_context, page_context};
+use wikisearch_gui::test_support::{FakeWiki, WikiGraph};
+use wikisearch_gui::{LinkCache, LinkContext, Searcher};
+
+#[test]
+fn finds_anchor_text_section_and_sentence() {
+    let html = "<html><body><h1>Start</h1><div id=\"mw-content-text\">\
+        <p>Start is a page.</p>\
+        <div class=\"mw-heading\"><h2 id=\"History\">History</h2><span class=\"mw-editsection\">[<a href=\"/w/index.php?action=edit\">edit</a>]</span></div>\
+        <p>It was founded in 1900.<sup class=\"reference\">[1]</sup> Later it moved to <a href=\"/wiki/Goal\" title=\"Goal\">the <b>goal</b> &amp; beyond</a> city. Nobody knows why.</p>\
+        </div></body></html>";
+
+    assert_eq!(link_context(html, "Start", "Goal"), Some(LinkContext {
+        from: "Start".to_string(),
+        to: "Goal".to_string(),
+        anchor_text: "the goal & beyond".to_string(),
+        section: "History".to_string(),
+        sentence: "Later it moved to the goal & beyond city.".to_string(),
+    }));
+    assert_eq!(link_context(html, "Start", "Other"), None);
+}
+
+#[test]
+fn page_context_matches_every_link() {
+    let html = "<div id=\"mw-content-text\"><p>Lead with <a href=\"/wiki/A\">a</a>.</p>\
+        <h2>One</h2><ul><li>Item <a href=\"/wiki/B\">b</a> here.</li></ul>\
+        <h3>Two</h3><p>First. Then <a href=\"/wiki/C\">c</a> and <a href=\"/wiki/A\">a again</a>. Last.</p>\
+        <table><tr><td><a href=\"/wiki/D\">d</a></td></tr></table></div>";
+
+    let context = page_context(html, "Start");
+    let titles: Vec<&str> = context.iter().map(|c| c.to.as_str()).collect();
+    assert_eq!(titles, ["A", "B", "C", "D"]);
+    for c in &context {
+        assert_eq!(Some(c), link_context(html, "Start", c.to.as_str()).as_ref());
+    }
+    assert_eq!((context[1].section.as_str(), context[1].sentence.as_str()), ("One", "Item b here."));
+    assert_eq!((context[2].section.as_str(), context[2].sentence.as_str()), ("Two", "Then c and a again."));
+}
+
+#[test]
+fn searcher_annotates_found_path() {
+    let wiki = FakeWiki::start(WikiGraph::new().chain(&["Start", "Middle", "Goal"]));
+    let report = Searcher::builder().source(wiki.wiki_url()).link_context(true).run("Start", "Goal");
+
+    let hops: Vec<(&str, &str, &str)> = report.context.iter().map(|c| (c.from.as_str(), c.to.as_str(), c.anchor_text.as_str())).collect();
+    assert_eq!(hops, vec![("Start", "Middle", "Middle"), ("Middle", "Goal", "Goal")]);
+    assert_eq!(report.context[0].sentence, "Start links to Middle,");
+    // The context comes with the pages the search fetched
+    assert_eq!((wiki.num_of_requests_to("Start"), wiki.num_of_requests_to("Middle")), (1, 1));
+
+    let report = Searcher::builder().source(wiki.wiki_url()).run("Start", "Goal");
+    assert!(report.context.is_empty());
+    assert_eq!(report.context_error, None);
+}
+
+#[test]
+fn reports_pages_whose_context_can_not_be_fetched() {
+    // The path is found in the cache, but the wiki does not have its pages
+    let wiki = FakeWiki::start(WikiGraph::new().page("Goal", &[]));
+    let cache = LinkCache::new(10);
+    cache.insert("Start", &["Goal".to_string()]);
+
+    let report = Searcher::builder().source(wiki.wiki_url()).cache(cache).link_context(true).run("Start", "Goal");
+    assert_eq!(report.outcome.path(), ["Start", "Goal"]);
+    assert!(report.context.is_empty());
+    assert!(report.context_error.unwrap().contains("Start"));
+}
+
+#[test]
+fn fetches_again_only_the_pages_from_the_cache() {
+    let wiki = FakeWiki::start(WikiGraph::new().chain(&["Start", "Middle", "Goal"]));
+    let cache = LinkCache::new(10);
+    cache.insert("Start", &["Middle".to_string()]);
+
+    let report = Searcher::builder().source(wiki.wiki_url()).cache(cache).link_context(true).run("Start", "Goal");
+    assert_eq!(report.context.len(), 2);
+    assert_eq!(report.context[0].anchor_text, "Middle");
+    assert_eq!(report.context_error, None);
+    assert_eq!((wiki.num_of_requests_to("Start"), wiki.num_of_requests_to("Middle")), (1, 1));
+}
+
+#[test]
+fn replays_recorded_context() {
+    let wiki = FakeWiki::start(WikiGraph::new().chain(&["Start", "Middle", "Goal"]));
+    let archive = Archive::new();
+    let recorded = Searcher::builder().source(wiki.wiki_url()).archive(ArchiveMode::Record(archive.clone())).link_context(true).run("Start", "Goal");
+
+    let path = std::env::temp_dir().join(format!("wikisearch-context-{}.json", std::process::id()));
+    archive.save(&path).unwrap();
+    let loaded = Archive::load(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let requests = wiki.num_of_requests();
+    let replayed = Searcher::builder().source(wiki.wiki_url()).archive(ArchiveMode::Replay(loaded)).link_context(true).run("Start", "Goal");
+    assert_eq!(replayed.context, recorded.context);
+    assert_eq!(replayed.context[1].sentence, "Middle links to Goal,");
+    assert_eq!(wiki.num_of_requests(), requests);
+}
@@ -0,0 +1,46 @@
+use std::sync::mpsc;
+
+use wikisearch_gui::first_link::{first_link, follow_first_links, ChainEnd};
+use wikisearch_gui::test_support::{FakeWiki, WikiGraph};
+use wikisearch_gui::FetchOptions;
+
+fn article(body: &str) -> String {
+    format!("<html><body><div id=\"mw-content-text\">{}</div></body></html>", body)
+}
+
+#[test]
+fn skips_parentheses_italics_and_namespaces() {
+    let html = article("<table><tr><td><a href=\"/wiki/Infobox\">infobox</a></td></tr></table>\
+        <p><b>Thing</b> (<a href=\"/wiki/Pronunciation\">say it</a>) is an <i><a href=\"/wiki/Italic\">italic</a></i> \
+        <a href=\"/wiki/Help:Thing\">help</a> <a href=\"/wiki/Object#Kinds\" title=\"Object\">object</a>.</p>");
+
+    assert_eq!(first_link(html.as_str()), Some("Object".to_string()));
+    assert_eq!(first_link(article("<p>No links here.</p>").as_str()), None);
+}
+
+#[test]
+fn follows_chain_until_target_or_loop() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "A", "B", "Philosophy", "Knowledge", "A"]));
+    let (sender, _reciever) = mpsc::channel();
+    let fetch = FetchOptions::default();
+
+    let res = follow_first_links("Start", "Philosophy", wiki.wiki_url().as_str(), &fetch, 0, sender.clone());
+    assert_eq!(res.end, ChainEnd::Target);
+    assert_eq!(res.chain, vec!["Start", "A", "B", "Philosophy"]);
+
+    let res = follow_first_links("Start", "Nowhere", wiki.wiki_url().as_str(), &fetch, 0, sender.clone());
+    assert_eq!(res.end, ChainEnd::Loop(1));
+
+    let res = follow_first_links("Start", "Nowhere", wiki.wiki_url().as_str(), &fetch, 2, sender.clone());
+    assert_eq!(res.end, ChainEnd::StepLimit);
+
+    let res = follow_first_links("Missing", "Philosophy", wiki.wiki_url().as_str(), &fetch, 0, sender.clone());
+    assert!(matches!(res.end, ChainEnd::Error(_)));
+
+    let requests = wiki.num_of_requests();
+    let fetch = FetchOptions { proxy: "not a proxy".to_string(), ..Default::default() };
+    let res = follow_first_links("Start", "Philosophy", wiki.wiki_url().as_str(), &fetch, 0, sender);
+    assert!(matches!(&res.end, ChainEnd::Error(e) if e.starts_with("Invalid network settings")), "{:?}", res.end);
+    assert_eq!(wiki.num_of_requests(), requests);
+}
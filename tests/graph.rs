@@ -0,0 +1,80 @@
+use wikisearch_gui::test_support::{FakeWiki, WikiGraph};
+use wikisearch_gui::{Constraints, GraphFormat, GraphRecording, Searcher};
+
+#[test]
+fn records_and_exports_explored_graph() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .page("Start", &["A", "B"])
+        .page("A", &["Start", "B", "Goal"])
+        .page("B", &["A"]));
+    let run = |recording| Searcher::builder().source(wiki.wiki_url()).graph_recording(recording).run("Start", "Goal");
+
+    assert!(run(GraphRecording::Off).graph.is_empty());
+
+    let graph = run(GraphRecording::Tree).graph;
+    let nodes: Vec<(&str, usize, Option<&str>)> = graph.nodes.iter().map(|n| (n.title.as_str(), n.depth, n.parent.as_deref())).collect();
+    assert_eq!(nodes, vec![("Start", 0, None), ("A", 1, Some("Start")), ("B", 1, Some("Start")), ("Goal", 2, Some("A"))]);
+    assert!(graph.links.is_empty());
+    assert_eq!(graph.path, vec!["Start", "A", "Goal"]);
+
+    let graph = run(GraphRecording::AllLinks).graph;
+    assert!(graph.links.contains(&("A".to_string(), "Start".to_string())));
+
+    let dot = graph.export(GraphFormat::Dot);
+    assert!(dot.starts_with("digraph"));
+    assert!(dot.contains("\"Goal\" [label=\"Goal\", comment=\"depth=2, leg=0\", color=red];"));
+    assert!(dot.contains("\"A\" -> \"Goal\" [color=red];"));
+    assert!(dot.contains("\"A\" -> \"Start\" [style=dashed];"));
+
+    let graphml = graph.export(GraphFormat::GraphMl);
+    assert!(graphml.contains("<node id=\"Goal\"><data key=\"depth\">2</data>"));
+
+    let json: serde_json::Value = serde_json::from_str(graph.export(GraphFormat::Json).as_str()).unwrap();
+    assert_eq!(json["nodes"].as_array().unwrap().len(), 4);
+    assert_eq!(json["edges"][0], serde_json::json!({ "from": "Start", "to": "A", "kind": "tree" }));
+}
+
+#[test]
+fn graphml_declares_every_linked_page() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .page("Start", &["Hidden", "A"])
+        .page("A", &["Goal"]));
+    let graph = Searcher::builder()
+        .source(wiki.wiki_url())
+        .graph_recording(GraphRecording::AllLinks)
+        .constraints(Constraints::parse("", "Hidden"))
+        .run("Start", "Goal")
+        .graph;
+    assert!(graph.nodes.iter().all(|n| n.title != "Hidden"));
+
+    let graphml = graph.export(GraphFormat::GraphMl);
+    assert!(graphml.contains("<edge source=\"Start\" target=\"Hidden\">"));
+    assert_eq!(graphml.matches("<node id=\"Hidden\"/>").count(), 1);
+    for edge in graphml.lines().filter(|l| l.contains("<edge ")) {
+        for end in ["source", "target"] {
+            let id = edge.split(format!("{}=\"", end).as_str()).nth(1).unwrap().split('"').next().unwrap();
+            assert!(graphml.contains(format!("<node id=\"{}\"", id).as_str()), "{} is not declared", id);
+        }
+    }
+}
+
+#[test]
+fn keeps_the_first_leg_of_pages_on_several_legs() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .page("Start", &["B", "Via"])
+        .page("Via", &["B"])
+        .page("B", &["Goal"]));
+    let graph = Searcher::builder()
+        .source(wiki.wiki_url())
+        .graph_recording(GraphRecording::Tree)
+        .constraints(Constraints::parse("Via", ""))
+        .run("Start", "Goal")
+        .graph;
+    assert_eq!(graph.path, vec!["Start", "Via", "B", "Goal"]);
+
+    let nodes: Vec<(&str, usize, usize, Option<&str>)> = graph.nodes.iter()
+        .map(|n| (n.title.as_str(), n.leg, n.depth, n.parent.as_deref()))
+        .collect();
+    assert_eq!(nodes.iter().filter(|n| n.0 == "B").count(), 1);
+    assert!(nodes.contains(&("B", 0, 1, Some("Start"))), "{:?}", nodes);
+}
@@ -0,0 +1,432 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use wikisearch_gui::search::{get_links, resolve_page, PageError};
+use wikisearch_gui::test_support::{FakeWiki, WikiGraph};
+use wikisearch_gui::{Archive, ArchiveMode, Constraints, FetchOptions, FrontierEvent, LinkCache, Limits, Observer, Progress, RateLimiter, SearchOutcome, Searcher};
+
+fn search(wiki: &FakeWiki, threads: usize, from: &str, to: &str) -> wikisearch_gui::SearchReport {
+    Searcher::builder().source(wiki.wiki_url()).threads(threads).run(from, to)
+}
+
+fn found(path: &[&str]) -> SearchOutcome {
+    SearchOutcome::Found(path.iter().map(|s| s.to_string()).collect())
+}
+
+#[test]
+fn finds_shortest_path() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "A", "B", "C", "Goal"])
+        .chain(&["Start", "D", "Goal"]));
+
+    for threads in [1, 4] {
+        assert_eq!(search(&wiki, threads, "Start", "Goal").outcome, found(&["Start", "D", "Goal"]));
+    }
+}
+
+#[test]
+fn accepts_urls_and_titles_with_spaces() {
+    let wiki = FakeWiki::start(WikiGraph::new().chain(&["New_York", "Big_Apple"]));
+    let from = format!("{}New_York", wiki.wiki_url());
+
+    assert_eq!(search(&wiki, 1, from.as_str(), "Big Apple").outcome, found(&["New_York", "Big_Apple"]));
+}
+
+#[test]
+fn reports_no_path() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "A", "B", "A"])
+        .page("Goal", &[]));
+
+    let report = search(&wiki, 2, "Start", "Goal");
+    assert_eq!(report.outcome, SearchOutcome::NoPath);
+    assert_eq!(report.progress.processed, 3);
+}
+
+#[test]
+fn extracts_only_article_links() {
+    let html = "<html><body>\
+        <div id=\"mw-navigation\"><a href=\"/wiki/Goal\">Goal</a></div>\
+        <div id=\"mw-content-text\"><p>\
+            <a href=\"/wiki/File:Goal.png\">image</a>\
+            <a href=\"/wiki/Help:Goal\">help</a>\
+            <a href=\"/w/index.php?title=Goal&action=edit\">edit</a>\
+            <a href=\"/wiki/Other\">other</a>\
+        </p></div></body></html>";
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .html("Start", html)
+        .page("Other", &[]));
+
+    assert_eq!(search(&wiki, 1, "Start", "Goal").outcome, SearchOutcome::NoPath);
+    assert_eq!(wiki.num_of_requests_to("Other"), 1);
+    assert_eq!(wiki.num_of_requests_to("File:Goal.png"), 0);
+}
+
+#[test]
+fn gets_links_of_a_page() {
+    let wiki = FakeWiki::start(WikiGraph::new().page("Start", &["A", "B"]));
+    let mut client = reqwest::blocking::Client::new();
+
+    assert_eq!(get_links(wiki.wiki_url().as_str(), "Start", &mut client).unwrap(), vec!["A", "B"]);
+    assert!(get_links(wiki.wiki_url().as_str(), "Missing", &mut client).unwrap().is_empty());
+}
+
+#[test]
+fn follows_redirects() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .page("Start", &["Alias"])
+        .redirect("Alias", "Middle")
+        .page("Middle", &["Goal"]));
+
+    assert_eq!(search(&wiki, 1, "Start", "Goal").outcome, found(&["Start", "Alias", "Goal"]));
+}
+
+#[test]
+fn missing_pages_are_dead_ends() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .page("Start", &["Missing", "Middle"])
+        .page("Middle", &["Goal"]));
+
+    let report = search(&wiki, 1, "Start", "Goal");
+    assert_eq!(report.outcome, found(&["Start", "Middle", "Goal"]));
+    assert!(report.dead_threads.is_empty());
+    assert_eq!(wiki.num_of_requests_to("Missing"), 1);
+}
+
+#[test]
+fn rate_limited_page_is_retried_by_another_thread() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "Middle", "Goal"])
+        .rate_limited("Middle", 1));
+
+    let report = search(&wiki, 2, "Start", "Goal");
+    assert_eq!(report.outcome, found(&["Start", "Middle", "Goal"]));
+    assert_eq!(report.dead_threads.len(), 1);
+    assert_eq!(wiki.num_of_requests_to("Middle"), 2);
+}
+
+#[test]
+fn fails_when_all_threads_are_dead() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "Middle", "Goal"])
+        .broken("Middle"));
+
+    let report = search(&wiki, 2, "Start", "Goal");
+    assert_eq!(report.outcome, SearchOutcome::Error("all threads are dead".to_string()));
+    let mut dead_threads = report.dead_threads.clone();
+    dead_threads.sort_unstable();
+    assert_eq!(dead_threads, vec![0, 1]);
+}
+
+#[test]
+fn observer_sees_progress_and_thread_deaths() {
+    #[derive(Default)]
+    struct Recorder {
+        progress: Vec<Progress>,
+        dead_threads: Vec<usize>,
+    }
+    impl Observer for &mut Recorder {
+        fn progress(&mut self, progress: &Progress) {
+            self.progress.push(progress.clone());
+        }
+        fn thread_died(&mut self, thread: usize) {
+            self.dead_threads.push(thread);
+        }
+    }
+
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "A", "B", "Goal"])
+        .rate_limited("A", 1));
+    let mut recorder = Recorder::default();
+    let report = Searcher::builder()
+        .source(wiki.wiki_url())
+        .threads(2)
+        .observer(&mut recorder)
+        .run("Start", "Goal");
+
+    assert_eq!(report.outcome, found(&["Start", "A", "B", "Goal"]));
+    assert_eq!(recorder.dead_threads, report.dead_threads);
+    assert_eq!(recorder.progress.last(), Some(&report.progress));
+    // Start, A and B, the goal is found among the links of B
+    assert_eq!(report.progress.processed, 3);
+    assert!(recorder.progress.windows(2).all(|p| p[0].processed <= p[1].processed));
+}
+
+#[test]
+fn respects_constraints() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "Short", "Goal"])
+        .chain(&["Start", "Long_1", "Long_2", "Goal"])
+        .chain(&["Start", "Detour", "Back", "Goal"]));
+
+    let report = Searcher::builder()
+        .source(wiki.wiki_url())
+        .constraints(Constraints::parse("", "Short"))
+        .run("Start", "Goal");
+    assert_eq!(report.outcome.path().len(), 4);
+    assert!(!report.outcome.path().contains(&"Short".to_string()));
+
+    let report = Searcher::builder()
+        .source(wiki.wiki_url())
+        .constraints(Constraints::parse("Back", "Long_*"))
+        .run("Start", "Goal");
+    assert_eq!(report.outcome, found(&["Start", "Detour", "Back", "Goal"]));
+}
+
+#[test]
+fn legs_do_not_pass_through_later_stops() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "Goal", "Via", "Goal"])
+        .chain(&["Start", "A", "B", "Via"]));
+
+    let report = Searcher::builder()
+        .source(wiki.wiki_url())
+        .constraints(Constraints::parse("Via", ""))
+        .run("Start", "Goal");
+    assert_eq!(report.outcome, found(&["Start", "A", "B", "Via", "Goal"]));
+}
+
+#[test]
+fn stops_at_limits() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "A", "B", "C", "Goal"])
+        .slow("B", Duration::from_millis(500)));
+    let run = |limits: Limits| Searcher::builder().source(wiki.wiki_url()).limits(limits).run("Start", "Goal").outcome;
+
+    assert!(matches!(run(Limits { max_depth: 2, ..Default::default() }), SearchOutcome::LimitReached(_)));
+    assert!(matches!(run(Limits { max_processed: 2, ..Default::default() }), SearchOutcome::LimitReached(_)));
+    assert!(matches!(run(Limits { max_duration: Some(Duration::from_millis(100)), ..Default::default() }), SearchOutcome::LimitReached(_)));
+    assert_eq!(run(Limits { max_depth: 4, ..Default::default() }), found(&["Start", "A", "B", "C", "Goal"]));
+}
+
+#[test]
+fn can_be_cancelled() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "A", "B", "Goal"])
+        .delay(Duration::from_millis(200)));
+
+    let report = Searcher::builder()
+        .source(wiki.wiki_url())
+        .cancel_flag(Arc::new(AtomicBool::new(true)))
+        .run("Start", "Goal");
+    assert_eq!(report.outcome, SearchOutcome::Cancelled);
+}
+
+#[test]
+fn shared_cache_saves_requests() {
+    let wiki = FakeWiki::start(WikiGraph::new().chain(&["Start", "A", "B", "Goal"]));
+    let mut searcher = Searcher::builder()
+        .source(wiki.wiki_url())
+        .cache(LinkCache::new(100))
+        .build();
+
+    assert_eq!(searcher.run("Start", "Goal").outcome, found(&["Start", "A", "B", "Goal"]));
+    let requests = wiki.num_of_requests();
+    assert_eq!(searcher.run("Start", "Goal").outcome, found(&["Start", "A", "B", "Goal"]));
+    assert_eq!(wiki.num_of_requests(), requests);
+}
+
+#[test]
+fn reports_frontier_when_watched() {
+    #[derive(Default)]
+    struct Frontier(Vec<FrontierEvent>);
+    impl Observer for &mut Frontier {
+        fn frontier(&mut self, event: &FrontierEvent) {
+            self.0.push(event.clone());
+        }
+    }
+
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .page("Start", &["A", "B"])
+        .page("A", &["Goal"]));
+    let mut frontier = Frontier::default();
+    Searcher::builder()
+        .source(wiki.wiki_url())
+        .watch_frontier(true)
+        .observer(&mut frontier)
+        .run("Start", "Goal");
+
+    let discovered: Vec<(&str, Option<&str>, usize)> = frontier.0.iter().filter_map(|e| match e {
+        FrontierEvent::Discovered { title, parent, depth } => Some((title.as_str(), parent.as_deref(), *depth)),
+        _ => None,
+    }).collect();
+    assert_eq!(discovered, vec![("Start", None, 0), ("A", Some("Start"), 1), ("B", Some("Start"), 1), ("Goal", Some("A"), 2)]);
+    let fetching = frontier.0.iter().filter(|e| matches!(e, FrontierEvent::Fetching { .. })).count();
+    let fetched = frontier.0.iter().filter(|e| matches!(e, FrontierEvent::Fetched { .. })).count();
+    assert_eq!(fetching, 2);
+    assert_eq!(fetched, 2);
+}
+
+#[test]
+fn resolves_pages_typed_by_the_user() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .page("New_York", &[])
+        .redirect("NYC", "New_York")
+        .rate_limited("Busy", usize::MAX)
+        .page("Busy", &[]));
+    let wiki_url = wiki.wiki_url();
+    let resolve = |page: &str| resolve_page(page, wiki_url.as_str(), &FetchOptions::default());
+
+    assert_eq!(resolve("New York"), Ok("New_York".to_string()));
+    assert_eq!(resolve(format!("{}New_York", wiki_url).as_str()), Ok("New_York".to_string()));
+    assert_eq!(resolve(format!("{}NYC", wiki_url).as_str()), Ok("New_York".to_string()));
+    assert_eq!(resolve("Boston"), Err(PageError::Missing));
+    assert_eq!(resolve("https://example.com/wiki/New_York"), Err(PageError::NotWikiUrl));
+    assert_eq!(resolve(" "), Err(PageError::Empty));
+    assert!(matches!(resolve("Busy"), Err(PageError::Network(_))));
+
+    let bad_proxy = FetchOptions { proxy: "not a proxy".to_string(), ..Default::default() };
+    assert!(matches!(resolve_page("New York", wiki_url.as_str(), &bad_proxy), Err(PageError::Network(_))));
+}
+
+#[test]
+fn reports_worker_status_and_restarts_dead_workers() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "Middle", "Goal"])
+        .rate_limited("Middle", 1));
+
+    let report = Searcher::builder()
+        .source(wiki.wiki_url())
+        .worker_restarts(1)
+        .run("Start", "Goal");
+    assert_eq!(report.outcome, found(&["Start", "Middle", "Goal"]));
+    assert_eq!(report.dead_threads, vec![0]);
+
+    let worker = &report.progress.workers[0];
+    assert_eq!((worker.pages_done, worker.errors, worker.retries, worker.restarts), (2, 1, 1, 1));
+    assert!(worker.last_error.as_deref().unwrap().contains("429"), "{:?}", worker.last_error);
+    assert!(worker.alive);
+    assert!(worker.page.is_none());
+    assert!(worker.last_fetch.is_some());
+}
+
+#[test]
+fn rate_limiter_is_shared_between_searches() {
+    let wiki = FakeWiki::start(WikiGraph::new().chain(&["A", "B", "C", "D"]));
+    // One request every 50 ms, the first one is free
+    let rate_limiter = RateLimiter::per_second(20.0);
+
+    let start = Instant::now();
+    for (from, to) in [("A", "D"), ("B", "D")] {
+        let report = Searcher::builder()
+            .source(wiki.wiki_url())
+            .threads(4)
+            .rate_limiter(rate_limiter.clone())
+            .run(from, to);
+        assert!(matches!(report.outcome, SearchOutcome::Found(_)));
+    }
+    assert!(start.elapsed() >= Duration::from_millis(200), "5 requests took {:?}", start.elapsed());
+}
+
+#[test]
+fn cache_is_saved_and_loaded() {
+    let wiki = FakeWiki::start(WikiGraph::new().chain(&["Start", "A", "Goal"]));
+    let cache = LinkCache::new(100);
+    Searcher::builder().source(wiki.wiki_url()).cache(cache.clone()).run("Start", "Goal");
+
+    let path = std::env::temp_dir().join(format!("wikisearch-cache-{}.json", std::process::id()));
+    cache.save(&path).unwrap();
+    let loaded = LinkCache::new(100);
+    assert_eq!(loaded.load(&path).unwrap(), cache.len());
+    let _ = std::fs::remove_file(&path);
+
+    let requests = wiki.num_of_requests();
+    let report = Searcher::builder().source(wiki.wiki_url()).cache(loaded).run("Start", "Goal");
+    assert_eq!(report.outcome, found(&["Start", "A", "Goal"]));
+    assert_eq!(wiki.num_of_requests(), requests);
+}
+
+#[test]
+fn invalid_proxy_fails_the_search() {
+    let wiki = FakeWiki::start(WikiGraph::new().chain(&["Start", "Goal"]));
+    let fetch = FetchOptions { proxy: "not a url".to_string(), ..FetchOptions::default() };
+    let report = Searcher::builder().source(wiki.wiki_url()).fetch(fetch).run("Start", "Goal");
+    assert!(matches!(report.outcome, SearchOutcome::Error(_)), "{:?}", report.outcome);
+}
+
+#[test]
+fn bench_results_have_nested_spans() {
+    let wiki = FakeWiki::start(WikiGraph::new().chain(&["Start", "A", "B", "Goal"]));
+    let path = std::env::temp_dir().join(format!("wikisearch-bench-{}.json", std::process::id()));
+    let report = Searcher::builder().source(wiki.wiki_url()).threads(2).bench_file(path.to_str().unwrap()).run("Start", "Goal");
+    assert_eq!(report.outcome, found(&["Start", "A", "B", "Goal"]));
+
+    let spans: serde_json::Value = serde_json::from_str(std::fs::read_to_string(&path).unwrap().as_str()).unwrap();
+    let _ = std::fs::remove_file(&path);
+    let span = |name: &str| spans.as_array().unwrap().iter().find(|s| s["name"] == name).cloned().unwrap();
+
+    let page = span("page");
+    assert_eq!(page["calls"], 3);
+    let fetch = page["children"].as_array().unwrap().iter().find(|s| s["name"] == "fetch").unwrap();
+    assert_eq!(fetch["calls"], 3);
+    assert!(fetch["min"].as_f64().unwrap() <= fetch["mean"].as_f64().unwrap());
+    assert!(fetch["mean"].as_f64().unwrap() <= fetch["max"].as_f64().unwrap());
+    for (lower, upper) in [("min", "p50"), ("p50", "p90"), ("p90", "p99"), ("p99", "max")] {
+        assert!(fetch[lower].as_f64().unwrap() <= fetch[upper].as_f64().unwrap(), "{} > {}", lower, upper);
+    }
+
+    let coordination = span("coordination");
+    let dedup = coordination["children"].as_array().unwrap().iter().find(|s| s["name"] == "dedup").unwrap();
+    assert_eq!(dedup["calls"], 3);
+
+    let (_, parse) = report.bench.tree().into_iter().find(|(depth, s)| *depth == 1 && s.name == "parse").unwrap();
+    assert_eq!(parse.calls, 3);
+    assert_eq!(parse.percentile(100.0), parse.max);
+}
+
+#[test]
+fn bench_writes_folded_stacks_and_trace() {
+    let wiki = FakeWiki::start(WikiGraph::new().chain(&["Start", "A", "B", "Goal"]));
+    let dir = std::env::temp_dir();
+    let folded = dir.join(format!("wikisearch-bench-{}.folded", std::process::id()));
+    let trace = dir.join(format!("wikisearch-bench-{}.trace", std::process::id()));
+
+    let report = Searcher::builder().source(wiki.wiki_url()).threads(2).bench_file(folded.to_str().unwrap()).run("Start", "Goal");
+    assert!(!report.bench.has_timeline());
+    let stacks = std::fs::read_to_string(&folded).unwrap();
+    let _ = std::fs::remove_file(&folded);
+    for line in stacks.lines() {
+        let (stack, micros) = line.rsplit_once(' ').unwrap();
+        assert!(!stack.is_empty());
+        assert!(micros.parse::<u64>().unwrap() > 0);
+    }
+    assert!(stacks.lines().any(|l| l.starts_with("page;fetch ")), "{}", stacks);
+
+    Searcher::builder().source(wiki.wiki_url()).threads(2).bench_file(trace.to_str().unwrap()).run("Start", "Goal");
+    let trace_json: serde_json::Value = serde_json::from_str(std::fs::read_to_string(&trace).unwrap().as_str()).unwrap();
+    let _ = std::fs::remove_file(&trace);
+    let events = trace_json["traceEvents"].as_array().unwrap();
+    let names: Vec<&str> = events.iter().filter(|e| e["ph"] == "M").map(|e| e["args"]["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["search", "worker 0", "worker 1"]);
+    let fetches: Vec<&serde_json::Value> = events.iter().filter(|e| e["ph"] == "X" && e["name"] == "fetch").collect();
+    assert_eq!(fetches.len(), 3);
+    assert!(fetches.iter().all(|e| e["tid"].as_u64().unwrap() >= 1 && e["dur"].as_f64().unwrap() >= 0.0));
+}
+
+#[test]
+fn recorded_search_is_replayed_without_fetching() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["Start", "A", "B", "Goal"])
+        .chain(&["Start", "C", "D"]));
+    let archive = Archive::new();
+    let recorded = Searcher::builder().source(wiki.wiki_url()).threads(2).archive(ArchiveMode::Record(archive.clone())).run("Start", "Goal");
+    assert_eq!(recorded.outcome, found(&["Start", "A", "B", "Goal"]));
+    assert_eq!(archive.get("Start").unwrap(), ["A", "C"]);
+
+    let path = std::env::temp_dir().join(format!("wikisearch-archive-{}.json", std::process::id()));
+    archive.save(&path).unwrap();
+    let loaded = Archive::load(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(loaded.len(), archive.len());
+
+    let requests = wiki.num_of_requests();
+    let replayed = Searcher::builder().source(wiki.wiki_url()).threads(2).archive(ArchiveMode::Replay(loaded.clone())).run("Start", "Goal");
+    assert_eq!(replayed.outcome, recorded.outcome);
+    assert_eq!(wiki.num_of_requests(), requests);
+
+    // Pages that were not recorded have no links
+    let report = Searcher::builder().source(wiki.wiki_url()).archive(ArchiveMode::Replay(loaded)).run("Start", "Elsewhere");
+    assert_eq!(report.outcome, SearchOutcome::NoPath);
+    assert_eq!(wiki.num_of_requests(), requests);
+}
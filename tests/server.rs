@@ -0,0 +1,213 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+
+use wikisearch_gui::search::SearchConfig;
+use wikisearch_gui::server::{JobStatus, Server, ServerConfig};
+use wikisearch_gui::test_support::{FakeWiki, WikiGraph};
+
+/// Starts a server that searches `wiki` and returns its base url.
+fn start_server(wiki: &FakeWiki, max_jobs: usize) -> String {
+    start_server_keeping(wiki, max_jobs, 100)
+}
+
+fn start_server_keeping(wiki: &FakeWiki, max_jobs: usize, max_finished_jobs: usize) -> String {
+    start_server_with(ServerConfig {
+        search: SearchConfig {
+            wiki_url: wiki.wiki_url(),
+            threads: 2,
+            ..Default::default()
+        },
+        max_jobs,
+        max_threads: 4,
+        max_finished_jobs,
+        max_connections: 16,
+    })
+}
+
+fn start_server_with(config: ServerConfig) -> String {
+    let server = Server::bind("127.0.0.1:0", config).unwrap();
+    let url = format!("http://{}", server.local_addr().unwrap());
+    thread::spawn(move || server.run());
+    url
+}
+
+fn start_search(client: &Client, url: &str, body: Value) -> reqwest::blocking::Response {
+    client.post(format!("{}/searches", url)).json(&body).send().unwrap()
+}
+
+fn wait_until_done(client: &Client, url: &str, id: u64) -> JobStatus {
+    let start = Instant::now();
+    loop {
+        let status: JobStatus = client.get(format!("{}/searches/{}", url, id)).send().unwrap().json().unwrap();
+        if status.state != "running" {
+            return status;
+        }
+        assert!(start.elapsed() < Duration::from_secs(10), "search did not finish");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn finds_path() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .page("Start", &["Dead_end"])
+        .chain(&["Start", "Middle", "Goal"]));
+    let url = start_server(&wiki, 4);
+    let client = Client::new();
+
+    let res = start_search(&client, &url, json!({ "from": "Start", "to": "Goal" }));
+    assert_eq!(res.status().as_u16(), 201);
+    let status: JobStatus = res.json().unwrap();
+    assert_eq!(status.state, "running");
+
+    let status = wait_until_done(&client, &url, status.id);
+    assert_eq!(status.state, "found");
+    assert_eq!(status.path, vec!["Start", "Middle", "Goal"]);
+
+    let all: Vec<JobStatus> = client.get(format!("{}/searches", url)).send().unwrap().json().unwrap();
+    assert_eq!(all.len(), 1);
+}
+
+#[test]
+fn streams_events_until_done() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["A", "B", "C", "D"])
+        .delay(Duration::from_millis(50)));
+    let url = start_server(&wiki, 4);
+    let client = Client::new();
+
+    let status: JobStatus = start_search(&client, &url, json!({ "from": "A", "to": "D" })).json().unwrap();
+    let mut events = String::new();
+    client.get(format!("{}/searches/{}/events", url, status.id)).send().unwrap()
+        .read_to_string(&mut events).unwrap();
+
+    let last = events.trim_end().rsplit("\n\n").next().unwrap();
+    assert!(last.starts_with("event: done\n"), "unexpected events: {}", events);
+    let done: JobStatus = serde_json::from_str(last.trim_start_matches("event: done\ndata: ")).unwrap();
+    assert_eq!(done.state, "found");
+    assert_eq!(done.path, vec!["A", "B", "C", "D"]);
+}
+
+#[test]
+fn limits_jobs_and_cancels() {
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["A", "B", "C", "D", "E", "F"])
+        .delay(Duration::from_millis(300)));
+    let url = start_server(&wiki, 1);
+    let client = Client::new();
+
+    let first: JobStatus = start_search(&client, &url, json!({ "from": "A", "to": "F" })).json().unwrap();
+    let res = start_search(&client, &url, json!({ "from": "B", "to": "F" }));
+    assert_eq!(res.status().as_u16(), 429);
+
+    let res = client.delete(format!("{}/searches/{}", url, first.id)).send().unwrap();
+    assert_eq!(res.status().as_u16(), 202);
+    let status = wait_until_done(&client, &url, first.id);
+    assert_eq!(status.state, "cancelled");
+
+    let res = client.delete(format!("{}/searches/{}", url, first.id)).send().unwrap();
+    assert_eq!(res.status().as_u16(), 409);
+
+    // The slot is free again
+    let res = start_search(&client, &url, json!({ "from": "E", "to": "F" }));
+    assert_eq!(res.status().as_u16(), 201);
+}
+
+#[test]
+fn rejects_bad_requests() {
+    let wiki = FakeWiki::start(WikiGraph::new());
+    let url = start_server(&wiki, 4);
+    let client = Client::new();
+
+    let res = client.post(format!("{}/searches", url)).body("{\"from\": \"A\"}").send().unwrap();
+    assert_eq!(res.status().as_u16(), 400);
+    for timeout in ["-1", "1e400"] {
+        let body = format!("{{\"from\": \"A\", \"to\": \"B\", \"timeout_secs\": {}}}", timeout);
+        let res = client.post(format!("{}/searches", url)).body(body).send().unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+    }
+    assert_eq!(client.get(format!("{}/searches/42", url)).send().unwrap().status().as_u16(), 404);
+    assert_eq!(client.get(format!("{}/searches/abc", url)).send().unwrap().status().as_u16(), 404);
+    assert_eq!(client.get(format!("{}/nothing", url)).send().unwrap().status().as_u16(), 404);
+
+    // Rejected before the body is read
+    let mut stream = TcpStream::connect(url.trim_start_matches("http://")).unwrap();
+    stream.write_all(b"POST /searches HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 413 "), "{}", response);
+    assert_eq!(wiki.num_of_requests(), 0);
+
+    // Requests the server reads to the end, so that closing the connection does not reset it before the response is read
+    let too_long_line = format!("GET /searches HTTP/1.1\r\nX-Long: {}", "a".repeat(8 * 1024 - 8));
+    let too_many_headers = format!("GET /searches HTTP/1.1\r\n{}", "X-Header: a\r\n".repeat(101));
+    for (request, status) in [
+        (too_long_line.as_str(), 431),
+        (too_many_headers.as_str(), 431),
+        ("POST /searches HTTP/1.1\r\nContent-Length: many\r\n\r\n", 400),
+        ("\r\n\r\n", 400),
+    ] {
+        assert!(raw_request(&url, request).starts_with(format!("HTTP/1.1 {} ", status).as_str()), "{}", status);
+    }
+}
+
+/// Sends `request` as is and returns the whole response.
+fn raw_request(url: &str, request: &str) -> String {
+    let mut stream = TcpStream::connect(url.trim_start_matches("http://")).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn limits_connections() {
+    let wiki = FakeWiki::start(WikiGraph::new());
+    let url = start_server_with(ServerConfig {
+        search: SearchConfig { wiki_url: wiki.wiki_url(), ..Default::default() },
+        max_jobs: 1,
+        max_threads: 1,
+        max_finished_jobs: 1,
+        max_connections: 2,
+    });
+    let addr = url.trim_start_matches("http://");
+
+    // Connections that never send their request hold their slot until the read timeout
+    let idle = [TcpStream::connect(addr).unwrap(), TcpStream::connect(addr).unwrap()];
+    thread::sleep(Duration::from_millis(100));
+    let mut response = String::new();
+    TcpStream::connect(addr).unwrap().read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 503 "), "{}", response);
+
+    // A request that gets a 503 is reset before it is read, hence the errors
+    drop(idle);
+    let client = Client::new();
+    let start = Instant::now();
+    while !client.get(format!("{}/searches", url)).send().map_or(false, |res| res.status().as_u16() == 200) {
+        assert!(start.elapsed() < Duration::from_secs(5), "slots were not given back");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn forgets_the_oldest_finished_searches() {
+    let wiki = FakeWiki::start(WikiGraph::new().chain(&["A", "B"]));
+    let url = start_server_keeping(&wiki, 4, 2);
+    let client = Client::new();
+
+    let mut ids = Vec::new();
+    for _ in 0..3 {
+        let status: JobStatus = start_search(&client, &url, json!({ "from": "A", "to": "B" })).json().unwrap();
+        wait_until_done(&client, &url, status.id);
+        ids.push(status.id);
+    }
+
+    let all: Vec<JobStatus> = client.get(format!("{}/searches", url)).send().unwrap().json().unwrap();
+    assert_eq!(all.iter().map(|s| s.id).collect::<Vec<_>>(), ids[1..]);
+    assert_eq!(client.get(format!("{}/searches/{}", url, ids[0])).send().unwrap().status().as_u16(), 404);
+}
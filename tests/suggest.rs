@@ -0,0 +1,32 @@
+use wikisearch_gui::suggest::{api_url, opensearch, suggest};
+use wikisearch_gui::test_support::{FakeWiki, WikiGraph};
+use wikisearch_gui::{FetchOptions, LinkCache, Searcher};
+
+#[test]
+fn suggests_titles_from_the_wiki_or_the_cache() {
+    assert_eq!(api_url("https://en.wikipedia.org/wiki/"), "https://en.wikipedia.org/w/api.php");
+
+    let wiki = FakeWiki::start(WikiGraph::new()
+        .chain(&["New_York", "New_Jersey", "Newark"])
+        .page("Boston", &[]));
+    assert_eq!(opensearch(wiki.wiki_url().as_str(), "new ", 10, &FetchOptions::default()).unwrap(), vec!["New Jersey", "New York"]);
+    assert_eq!(opensearch(wiki.wiki_url().as_str(), "NEW", 2, &FetchOptions::default()).unwrap(), vec!["New Jersey", "New York"]);
+
+    let cache = LinkCache::new(100);
+    Searcher::builder().source(wiki.wiki_url()).cache(cache.clone()).run("New_York", "Newark");
+    let wiki_url = wiki.wiki_url();
+    drop(wiki);
+    assert_eq!(suggest(wiki_url.as_str(), "new j", &cache, 10, &FetchOptions::default()), vec!["New Jersey"]);
+}
+
+#[test]
+fn cache_titles_match_prefixes_in_any_case() {
+    let cache = LinkCache::new(100);
+    cache.insert("New_York", &["New_Jersey".to_string(), "Newark".to_string(), "Boston".to_string()]);
+    cache.insert("Newark", &["New_York".to_string()]);
+
+    assert_eq!(cache.titles_with_prefix("new ", 10), vec!["New_Jersey", "New_York"]);
+    assert_eq!(cache.titles_with_prefix("NEW", 2), vec!["New_Jersey", "New_York"]);
+    assert_eq!(cache.titles_with_prefix("newark", 10), vec!["Newark"]);
+    assert!(cache.titles_with_prefix("Newarks", 10).is_empty());
+}